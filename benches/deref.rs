@@ -0,0 +1,47 @@
+//! Compares `Deref`-heavy loops over `small::String` and `std::string::String`.
+//!
+//! Run with `cargo bench --bench deref`.
+
+extern crate small;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const STRINGS: usize = 10_000;
+const ROUNDS: usize = 2_000;
+
+/// Every other string is too long for the stack, so both storage paths are
+/// taken in the loop.
+fn inputs() -> Vec<std::string::String> {
+    (0..STRINGS)
+        .map(|i| if i % 2 == 0 {
+            format!("short {}", i)
+        } else {
+            format!("a string that is long enough for the heap {}", i)
+        })
+        .collect()
+}
+
+fn time<S: std::ops::Deref<Target = str>>(strings: &[S]) -> Duration {
+    let start = Instant::now();
+    let mut total = 0usize;
+    for _ in 0..ROUNDS {
+        for s in black_box(strings) {
+            total = total.wrapping_add(s.len() + s.as_bytes()[0] as usize);
+        }
+    }
+    black_box(total);
+    start.elapsed()
+}
+
+fn main() {
+    let std_strings = inputs();
+    let small_strings: Vec<small::String> = std_strings.iter().map(|s| s.as_str().into()).collect();
+    for _ in 0..3 {
+        println!(
+            "small::String {:>10.2?}    std::string::String {:>10.2?}",
+            time(&small_strings),
+            time(&std_strings)
+        );
+    }
+}
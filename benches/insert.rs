@@ -0,0 +1,37 @@
+//! Times inserting at the midpoint of a full 23-byte string, which spills it
+//! to the heap, compared with `std::string::String::insert`.
+//!
+//! Run with `cargo bench --bench insert`.
+
+extern crate small;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const FULL: &str = "abcdefghijklmnopqrstuvw";
+const ROUNDS: usize = 1_000_000;
+
+fn time<S, N, F>(mut new: N, mut f: F) -> Duration
+    where N: FnMut() -> S, F: FnMut(&mut S)
+{
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let mut s = new();
+        f(black_box(&mut s));
+        black_box(&s);
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let mid = FULL.len() / 2;
+    for _ in 0..3 {
+        let small_char = time(|| small::String::from(FULL), |s| s.insert(mid, '€'));
+        let small_str = time(|| small::String::from(FULL), |s| s.insert_str(mid, "€"));
+        let std_char = time(|| std::string::String::from(FULL), |s| s.insert(mid, '€'));
+        println!(
+            "small insert {:>10.2?}    small insert_str {:>10.2?}    std insert {:>10.2?}",
+            small_char, small_str, std_char
+        );
+    }
+}
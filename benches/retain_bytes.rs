@@ -0,0 +1,32 @@
+//! Compares `retain_bytes` against `retain` when stripping spaces from a long
+//! ASCII string.
+//!
+//! Run with `cargo bench --bench retain_bytes`.
+
+extern crate small;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ROUNDS: usize = 20;
+
+fn time<F: FnMut(&mut small::String)>(input: &str, mut f: F) -> Duration {
+    let mut elapsed = Duration::new(0, 0);
+    for _ in 0..ROUNDS {
+        let mut s = small::String::from(input);
+        let start = Instant::now();
+        f(black_box(&mut s));
+        elapsed += start.elapsed();
+        black_box(&s);
+    }
+    elapsed / ROUNDS as u32
+}
+
+fn main() {
+    let input = "the quick brown fox jumps over the lazy dog ".repeat(20_000);
+    for _ in 0..3 {
+        let chars = time(&input, |s| s.retain(|c| c != ' '));
+        let bytes = time(&input, |s| s.retain_bytes(|b| b != b' '));
+        println!("retain {:>10.2?}    retain_bytes {:>10.2?}", chars, bytes);
+    }
+}
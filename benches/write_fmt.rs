@@ -0,0 +1,92 @@
+//! Counts the allocations made when formatting a multi-field struct into a
+//! `small::String` with `write!`, which goes through the overridden
+//! `write_fmt`, and with `std::fmt::write`, which only calls `write_str`.
+//!
+//! Run with `cargo bench --bench write_fmt`.
+
+extern crate small;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts every allocation and reallocation made through the global allocator.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+struct Record {
+    id: u32,
+    name: &'static str,
+    email: &'static str,
+    score: f64,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{} {} <{}> scored {:.2}", self.id, self.name, self.email, self.score)
+    }
+}
+
+const RECORDS: u32 = 10_000;
+
+fn record(id: u32) -> Record {
+    Record { id, name: "Ada Lovelace", email: "ada@example.com", score: 98.5 }
+}
+
+/// Formats every record onto the end of one string.
+fn count_appending<F: FnMut(&mut small::String, &Record)>(mut f: F) -> usize {
+    let mut s = small::String::new();
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for id in 0..RECORDS {
+        f(&mut s, &record(id));
+        s.push('\n');
+    }
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+/// Formats each record into its own string that starts with a small heap
+/// buffer.
+fn count_fresh<F: FnMut(&mut small::String, &Record)>(mut f: F) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for id in 0..RECORDS {
+        let mut s = small::String::with_capacity(24);
+        f(&mut s, &record(id));
+    }
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn main() {
+    let write = |s: &mut small::String, r: &Record| write!(s, "{}", r).unwrap();
+    let std_write = |s: &mut small::String, r: &Record| fmt::write(s, format_args!("{}", r)).unwrap();
+    println!("{} records", RECORDS);
+    println!(
+        "appending:   write! {:>6} allocations    std::fmt::write {:>6} allocations",
+        count_appending(write),
+        count_appending(std_write)
+    );
+    println!(
+        "fresh:       write! {:>6} allocations    std::fmt::write {:>6} allocations",
+        count_fresh(write),
+        count_fresh(std_write)
+    );
+}
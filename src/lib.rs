@@ -9,7 +9,8 @@
 //! Currently, only the [`String`] type has been implemented. This is based
 //! upon similar implementations of `String` in various libc++ libraries.
 //! `String` stores 23 bytes of data on the stack, however once it begins to
-//! use the heap to store data, it never returns to using the stack.
+//! use the heap to store data, it only returns to using the stack when
+//! explicitly shrunk.
 //!
 //! [`String`]: string/struct.String.html
 
@@ -23,21 +24,43 @@ extern crate alloc;
 #[cfg(not(feature = "std"))]
 mod std {
     pub use core::*;
-    pub use alloc::{alloc, boxed, string, vec};
+    pub use alloc::{alloc, borrow, boxed, string, vec};
 }
 
 #[cfg(all(feature = "std", feature = "serde"))]
 extern crate serde;
 
+#[cfg(feature = "bytes")]
+extern crate bytes;
+
 pub mod string;
 pub use string::String;
 
 mod allocate {
     use std::{
-        alloc::{Layout, alloc as std_alloc, dealloc as std_dealloc, realloc as std_realloc},
+        alloc::{Layout, alloc as std_alloc, dealloc as std_dealloc, handle_alloc_error, realloc as std_realloc},
         mem,
     };
 
+    #[cfg(all(test, feature = "std"))]
+    thread_local! {
+        /// When set, every allocation made on this thread fails, so that tests
+        /// can exercise the allocation failure paths
+        pub static FAIL: ::std::cell::Cell<bool> = const { ::std::cell::Cell::new(false) };
+    }
+
+    #[cfg(all(test, feature = "std"))]
+    #[inline(always)]
+    fn should_fail() -> bool {
+        FAIL.with(|fail| fail.get())
+    }
+
+    #[cfg(not(all(test, feature = "std")))]
+    #[inline(always)]
+    fn should_fail() -> bool {
+        false
+    }
+
     /// Allocate `count` number of `T` on the heap.
     ///
     /// Returns
@@ -45,20 +68,78 @@ mod allocate {
     /// A null pointer on failure, a valid pointer on success
     #[inline(always)]
     pub fn alloc<T>(count: usize) -> *mut T {
+        if should_fail() {
+            return ::std::ptr::null_mut();
+        }
         unsafe {
             std_alloc(Layout::from_size_align_unchecked(mem::size_of::<T>() * count, mem::align_of::<T>())) as _
         }
     }
 
-    /// Deallocate `ptr` of count `count`
+    /// Allocate `count` number of `T` on the heap, aborting on failure.
     #[inline(always)]
-    pub unsafe fn dealloc<T>(ptr: *mut T, count: usize) {
-        std_dealloc(ptr as *mut u8, Layout::from_size_align_unchecked(mem::size_of::<T>() * count, mem::align_of::<T>()));
+    pub fn alloc_or_abort<T>(count: usize) -> *mut T {
+        let ptr: *mut T = alloc(count);
+        if ptr.is_null() {
+            oom::<T>(count)
+        }
+        ptr
+    }
+
+    /// Report a failure to allocate `count` number of `T` and abort.
+    #[cold]
+    pub fn oom<T>(count: usize) -> ! {
+        handle_alloc_error(unsafe {
+            Layout::from_size_align_unchecked(mem::size_of::<T>() * count, mem::align_of::<T>())
+        })
+    }
+
+    /// Allocate a buffer with the given `layout` on the heap.
+    ///
+    /// Returns
+    /// -------
+    /// A null pointer on failure, a valid pointer on success
+    #[inline(always)]
+    pub fn alloc_aligned(layout: Layout) -> *mut u8 {
+        if should_fail() {
+            return ::std::ptr::null_mut();
+        }
+        unsafe {
+            std_alloc(layout)
+        }
+    }
+
+    /// Deallocate `ptr`, which was allocated with `layout`
+    #[inline(always)]
+    pub unsafe fn dealloc_aligned(ptr: *mut u8, layout: Layout) {
+        std_dealloc(ptr, layout);
+    }
+
+    /// Reallocate `ptr`, which was allocated with `layout`, to be `size`
+    /// bytes, keeping the alignment. `size` must form a valid `Layout` with
+    /// that alignment.
+    ///
+    /// Returns
+    /// -------
+    /// A null pointer on failure, in which case `ptr` is left untouched
+    #[inline(always)]
+    pub unsafe fn realloc_aligned(ptr: *mut u8, layout: Layout, size: usize) -> *mut u8 {
+        if should_fail() {
+            return ::std::ptr::null_mut();
+        }
+        std_realloc(ptr, layout, size)
     }
 
     /// Reallocate `ptr` with count `old_count` to be of size `new_count`
+    ///
+    /// Returns
+    /// -------
+    /// A null pointer on failure, in which case `ptr` is left untouched
     #[inline(always)]
     pub unsafe fn realloc<T>(ptr: *mut T, old_count: usize, count: usize) -> *mut T {
+        if should_fail() {
+            return ::std::ptr::null_mut();
+        }
         std_realloc(ptr as *mut u8, Layout::from_size_align_unchecked(mem::size_of::<T>() * old_count, mem::align_of::<T>()), mem::size_of::<T>() * count) as _
     }
 }
@@ -1,5 +1,6 @@
 use super::{allocate as alloc, std};
-use std::borrow::Borrow;
+use std::alloc::Layout;
+use std::borrow::{Borrow, Cow};
 use std::hint::unreachable_unchecked;
 
 #[cfg(all(feature = "serde", feature = "std"))]
@@ -14,7 +15,10 @@ enum Inner {
     },
     Heap {
         capacity: usize,
-        data: *mut u8
+        data: *mut u8,
+        /// The buffer is aligned to `1 << align_shift` bytes. This is `0` for
+        /// every buffer that is not from `with_capacity_aligned`.
+        align_shift: u8
     }
 }
 
@@ -24,6 +28,80 @@ unsafe impl Send for Inner {}
 /// Inner is safe to sync between threads
 unsafe impl Sync for Inner {}
 
+/// Returns a word with the high bit set in each byte of the eight byte
+/// `chunk` that is equal to `b`, and every other bit clear.
+///
+/// The bytes are read in little endian order, so the index of the first
+/// match is the number of trailing zeros divided by eight.
+#[inline]
+fn swar_matches(chunk: &[u8], b: u8) -> u64 {
+    const LO: u64 = 0x7f7f_7f7f_7f7f_7f7f;
+    const HI: u64 = 0x8080_8080_8080_8080;
+    let mut word = [0; 8];
+    word.copy_from_slice(chunk);
+    // Matching bytes become zero, then every non-zero byte gets its high
+    // bit set. The bytes left without a high bit are matches.
+    let x = u64::from_le_bytes(word) ^ (u64::from(b) * 0x0101_0101_0101_0101);
+    let nonzero = ((x & LO) + LO) | x;
+    !nonzero & HI
+}
+
+#[cfg(feature = "debug-alloc")]
+static GROWTH_HOOK: std::sync::atomic::AtomicPtr<()> =
+    std::sync::atomic::AtomicPtr::new(::std::ptr::null_mut());
+
+/// Sets a callback that is called with the old and new capacity whenever a
+/// `String` moves onto the heap or grows its heap buffer. A string on the
+/// stack reports an old capacity of 23.
+///
+/// The callback is global and runs on the thread that grew the string.
+/// Passing `None` removes it.
+///
+/// # Examples
+///
+/// Basic usage:
+/// ```
+/// # extern crate small;
+/// use small::String;
+/// use small::string::set_growth_hook;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// static GROWTHS: AtomicUsize = AtomicUsize::new(0);
+///
+/// fn count(_old: usize, _new: usize) {
+///     GROWTHS.fetch_add(1, Ordering::Relaxed);
+/// }
+///
+/// set_growth_hook(Some(count));
+/// let mut s = String::from("Hello");
+/// s.push_str(", this no longer fits on the stack");
+/// set_growth_hook(None);
+///
+/// assert_eq!(1, GROWTHS.load(Ordering::Relaxed));
+/// ```
+#[cfg(feature = "debug-alloc")]
+pub fn set_growth_hook(hook: Option<fn(usize, usize)>) {
+    let hook = match hook {
+        Some(f) => f as *mut (),
+        None => ::std::ptr::null_mut()
+    };
+    GROWTH_HOOK.store(hook, std::sync::atomic::Ordering::Release);
+}
+
+#[cfg(feature = "debug-alloc")]
+#[inline]
+fn report_growth(old: usize, new: usize) {
+    let hook = GROWTH_HOOK.load(std::sync::atomic::Ordering::Acquire);
+    if !hook.is_null() {
+        let hook: fn(usize, usize) = unsafe { ::std::mem::transmute(hook) };
+        hook(old, new);
+    }
+}
+
+#[cfg(not(feature = "debug-alloc"))]
+#[inline(always)]
+fn report_growth(_old: usize, _new: usize) {}
+
 ///
 /// A UTF-8 encoded, growable string which stores up to 23 bytes on the stack.
 ///
@@ -37,36 +115,87 @@ unsafe impl Sync for Inner {}
 /// You can also "import" a [`std::string::String`] using the [`from_string`]
 /// method, which will not perform any allocations or deallocations.
 ///
+/// # Map keys
+///
+/// `small::String` implements [`Borrow<str>`], so a `HashMap<small::String, V>`
+/// can be queried with a [`&str`]. This does not carry through smart pointers:
+/// `Arc<small::String>` only borrows as `small::String`, so a
+/// `HashMap<Arc<small::String>, V>` has to be queried with a
+/// `&small::String`. Building that key from a [`&str`] of 23 bytes or less
+/// does not allocate.
+///
 /// # Warning
 ///
-/// Once `small::String` begins to allocate on the heap, it will never revert to
-/// using the stack for storage.
+/// Once `small::String` begins to allocate on the heap, it will not revert to
+/// using the stack for storage unless explicitly asked to, for example with
+/// [`shrink_to_fit`].
 ///
+/// [`Borrow<str>`]: https://doc.rust-lang.org/nightly/std/borrow/trait.Borrow.html
 /// [`std::string::String`]: https://doc.rust-lang.org/nightly/std/string/struct.String.html
 /// [`&str`]: https://doc.rust-lang.org/nightly/std/primitive.str.html
 /// [`from_string`]: #method.from_string
+/// [`shrink_to_fit`]: #method.shrink_to_fit
 ///
 pub struct String {
     len: usize,
     inner: Inner
 }
 
+/// The storage of a `String`, as returned by [`String::as_parts`].
+///
+/// [`String::as_parts`]: struct.String.html#method.as_parts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Storage<'a> {
+    /// The string is stored inline on the stack
+    Inline(&'a [u8]),
+    /// The string is stored in a heap allocation of `capacity` bytes
+    Heap {
+        capacity: usize,
+        bytes: &'a [u8]
+    }
+}
+
 impl fmt::Debug for String {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "On {}: '{}'",
-            if let Inner::Stack { .. } = self.inner {
-                "stack"
-            } else {
-                "heap"
-            },
-            self,
-        )
+        // The alternate form also shows where the string is stored
+        if f.alternate() {
+            let storage = match self.inner {
+                Inner::Stack { .. } => "stack",
+                Inner::Heap { .. } => "heap"
+            };
+            f.debug_struct("String")
+                .field("storage", &storage)
+                .field("len", &self.len)
+                .field("capacity", &self.capacity())
+                .field("value", &self.as_str())
+                .finish()
+        } else {
+            fmt::Debug::fmt(self.as_str(), f)
+        }
     }
 }
 
 impl String {
+    /// An empty `String` on the stack.
+    ///
+    /// This is the same as [`new`], but can be named directly in constant
+    /// expressions such as struct field initializers.
+    ///
+    /// [`new`]: #method.new
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// const NAME: String = String::EMPTY;
+    ///
+    /// assert_eq!("", NAME);
+    /// ```
+    pub const EMPTY: String = String::new();
+
     /// Creates a new empty `String`.
     ///
     /// This will create a a string that starts on the stack. If you want to
@@ -86,7 +215,7 @@ impl String {
     /// let s = String::new();
     /// ```
     #[inline]
-    pub fn new() -> String {
+    pub const fn new() -> String {
         String {
             len: 0,
             inner: Inner::Stack {
@@ -136,12 +265,75 @@ impl String {
     /// ```
     #[inline]
     pub fn with_capacity(capacity: usize) -> String {
-        assert!(capacity != 0);
+        if capacity == 0 {
+            return String::new();
+        }
+        String {
+            len: 0,
+            inner: Inner::Heap {
+                capacity,
+                data: alloc::alloc_or_abort(capacity),
+                align_shift: 0
+            }
+        }
+    }
+
+    /// Creates a new empty `String` on the heap whose buffer is aligned to
+    /// `align` bytes.
+    ///
+    /// This is useful for SIMD routines that require an aligned buffer, as
+    /// they can then operate on [`as_ptr`] directly instead of on a copy. The
+    /// alignment is kept when the buffer grows or shrinks, but is lost if the
+    /// string moves back onto the stack, for example with [`shrink_to_fit`].
+    /// Cloning a string of 23 bytes or less also loses it, as the clone is
+    /// stored on the stack.
+    /// Converting an aligned `String` into a `Vec<u8>` or a `Box<str>` copies
+    /// the data into an ordinary buffer.
+    ///
+    /// If the given capacity is `0`, a buffer of `align` bytes is allocated,
+    /// so that the string is still on the heap.
+    ///
+    /// [`as_ptr`]: #method.as_ptr
+    /// [`shrink_to_fit`]: #method.shrink_to_fit
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two, or if `capacity` rounded up
+    /// to a multiple of `align` overflows [`isize`].
+    ///
+    /// [`isize`]: https://doc.rust-lang.org/nightly/std/primitive.isize.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::with_capacity_aligned(64, 32);
+    /// s.push_str("hello");
+    ///
+    /// assert_eq!(s.as_ptr() as usize % 32, 0);
+    /// assert_eq!(s, "hello");
+    /// ```
+    #[inline]
+    pub fn with_capacity_aligned(capacity: usize, align: usize) -> String {
+        assert!(align.is_power_of_two(), "alignment must be a power of two");
+        let capacity = ::std::cmp::max(capacity, align);
+        let layout = match Layout::from_size_align(capacity, align) {
+            Ok(layout) => layout,
+            Err(_) => panic!("capacity overflow")
+        };
+        let data = alloc::alloc_aligned(layout);
+        if data.is_null() {
+            alloc::oom::<u8>(capacity)
+        }
         String {
             len: 0,
             inner: Inner::Heap {
                 capacity,
-                data: alloc::alloc(capacity)
+                data,
+                align_shift: align.trailing_zeros() as u8
             }
         }
     }
@@ -168,13 +360,44 @@ impl String {
             len: string.len(),
             inner: Inner::Heap {
                 capacity: string.capacity(),
-                data: string.as_mut_ptr()
+                data: string.as_mut_ptr(),
+                align_shift: 0
             }
         };
         ::std::mem::forget(string);
         s
     }
 
+    /// Creates a `String` from a `std::string::String`, storing it on the
+    /// stack if it fits.
+    ///
+    /// Strings of 23 bytes or less are copied onto the stack and the old
+    /// string's buffer is freed. Longer strings reuse the old buffer without
+    /// copying, as in [`from_string`].
+    ///
+    /// [`from_string`]: #method.from_string
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// let s = small::String::from_string_compact("Hello!".into());
+    /// assert_eq!(s, "Hello!");
+    /// assert!(!s.overflowed());
+    ///
+    /// let s = small::String::from_string_compact("abcdefghijklmnopqrstuvwxyz".into());
+    /// assert!(s.overflowed());
+    /// ```
+    #[inline]
+    pub fn from_string_compact(string: std::string::String) -> String {
+        if string.len() <= 23 {
+            String::from(string.as_str())
+        } else {
+            String::from_string(string)
+        }
+    }
+
     /// Shortens this `String` to the specified length.
     ///
     /// If `new_len` is greater than the string's current length, this has no
@@ -283,6 +506,72 @@ impl String {
         }
     }
 
+    /// Returns the number of bytes this string has allocated on the heap, or
+    /// `0` if it is stored on the stack.
+    ///
+    /// Summing this over a collection of strings estimates how much memory
+    /// they hold from the allocator.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// # use small::String;
+    /// let stack = String::from("hello");
+    /// let heap = String::with_capacity(64);
+    ///
+    /// assert_eq!(0, stack.heap_bytes_allocated());
+    /// assert_eq!(64, heap.heap_bytes_allocated());
+    /// ```
+    #[inline]
+    pub fn heap_bytes_allocated(&self) -> usize {
+        match self.inner {
+            Inner::Stack { .. } => 0,
+            Inner::Heap { capacity, .. } => capacity
+        }
+    }
+
+    /// Returns where this string is stored along with its bytes
+    ///
+    /// This combines [`overflowed`], [`capacity`] and [`as_bytes`] into a single
+    /// call that can be matched on.
+    ///
+    /// [`overflowed`]: #method.overflowed
+    /// [`capacity`]: #method.capacity
+    /// [`as_bytes`]: #method.as_bytes
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// use small::string::Storage;
+    ///
+    /// let s = String::from("hi");
+    /// assert_eq!(Storage::Inline(b"hi"), s.as_parts());
+    ///
+    /// let s = String::from("abcdefghijklmnopqrstuvwxyz");
+    /// match s.as_parts() {
+    ///     Storage::Heap { capacity, bytes } => {
+    ///         assert_eq!(32, capacity);
+    ///         assert_eq!(b"abcdefghijklmnopqrstuvwxyz", bytes);
+    ///     },
+    ///     Storage::Inline(_) => unreachable!(),
+    /// }
+    /// ```
+    #[inline]
+    pub fn as_parts(&self) -> Storage<'_> {
+        match self.inner {
+            Inner::Stack { .. } => Storage::Inline(self.as_bytes()),
+            Inner::Heap { capacity, .. } => Storage::Heap {
+                capacity,
+                bytes: self.as_bytes()
+            }
+        }
+    }
+
     /// Removes the last character from the string buffer and returns it.
     ///
     /// Returns [`None`] if this `String` is empty.
@@ -311,6 +600,76 @@ impl String {
         Some(ch)
     }
 
+    /// Removes the last `n` characters from the string buffer and returns
+    /// them in their original order.
+    ///
+    /// If the string has fewer than `n` characters, the whole string is
+    /// removed and returned.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from("a€b€");
+    ///
+    /// assert_eq!(s.pop_str(2), "b€");
+    /// assert_eq!(s, "a€");
+    ///
+    /// assert_eq!(s.pop_str(5), "a€");
+    /// assert_eq!(s, "");
+    /// ```
+    #[inline]
+    pub fn pop_str(&mut self, n: usize) -> String {
+        if n == 0 {
+            return String::new();
+        }
+        let idx = match self.char_indices().rev().nth(n - 1) {
+            Some((idx, _)) => idx,
+            None => 0
+        };
+        let tail = String::from(&self[idx..]);
+        self.len = idx;
+        tail
+    }
+
+    /// Splits the string into two at the given byte index.
+    ///
+    /// Returns a newly allocated `String`. `self` contains bytes `[0, at)`, and
+    /// the returned `String` contains bytes `[at, len)`. `at` must be on the
+    /// boundary of a UTF-8 code point.
+    ///
+    /// Note that the capacity of `self` does not change. The returned `String`
+    /// is stored on the stack if it fits in 23 bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is not on a `UTF-8` code point boundary, or if it is
+    /// beyond the last code point of the string.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut hello = String::from("Hello, World!");
+    /// let world = hello.split_off(7);
+    ///
+    /// assert_eq!(hello, "Hello, ");
+    /// assert_eq!(world, "World!");
+    /// ```
+    #[inline]
+    pub fn split_off(&mut self, at: usize) -> String {
+        assert!(self.is_char_boundary(at));
+        let tail = String::from(&self[at..]);
+        self.len = at;
+        tail
+    }
+
     /// Removes a [`char`] from this `String` at a byte position and returns it.
     ///
     /// This is an `O(n)` operation, as it requires copying every element in the
@@ -362,142 +721,220 @@ impl String {
         ch
     }
 
-    #[inline]
-    fn as_ptr(&self) -> *const u8 {
-        match &self.inner {
-            Inner::Stack { ref data } => {
-                data as *const _ as _
-            },
-            Inner::Heap { capacity: _, ref data } => {
-                *data
-            }
-        }
-    }
-
-    #[inline]
-    fn as_mut_ptr(&mut self) -> *mut u8 {
-        match &mut self.inner {
-            Inner::Stack { ref mut data } => {
-                data as *mut _ as _
-            },
-            Inner::Heap { capacity: _, ref data } => {
-                *data
-            }
-        }
-    }
-
-    /// Retains only the characters specified by the predicate.
+    /// Inserts a character into this `String` at a byte position.
     ///
-    /// In other words, remove all characters `c` such that `f(c)` returns `false`.
-    /// This method operates in place and preserves the order of the retained
-    /// characters.
+    /// This is an `O(n)` operation as it requires copying every element in the
+    /// buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is larger than the `String`'s length, or if it does not
+    /// lie on a [`char`] boundary.
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
     ///
     /// # Examples
     ///
     /// Basic usage:
+    ///
     /// ```
     /// # extern crate small;
     /// use small::String;
-    /// let mut s = String::from("f_o_ob_ar");
+    /// let mut s = String::with_capacity(3);
     ///
-    /// s.retain(|c| c != '_');
+    /// s.insert(0, 'f');
+    /// s.insert(1, 'o');
+    /// s.insert(2, 'o');
     ///
-    /// assert_eq!(s, "foobar");
+    /// assert_eq!("foo", s);
     /// ```
     #[inline]
-    pub fn retain<F>(&mut self, mut f: F)
-        where F: FnMut(char) -> bool
-    {
-        use std::ptr;
-        let mut del_bytes = 0;
-        let mut idx = 0;
-
-        while idx < self.len {
-            let ch = unsafe {
-                self.get_unchecked(idx..self.len).chars().next().unwrap()
-            };
-            let ch_len = ch.len_utf8();
-
-            if !f(ch) {
-                del_bytes += ch_len;
-            } else if del_bytes > 0 {
-                unsafe {
-                    ptr::copy(self.as_ptr().offset(idx as isize),
-                              self.as_mut_ptr().offset((idx - del_bytes) as isize),
-                              ch_len);
-                }
-            }
-
-            // Point idx to the next char
-            idx += ch_len;
-        }
+    pub fn insert(&mut self, idx: usize, ch: char) {
+        assert!(self.is_char_boundary(idx));
+        let mut bits = [0; 4];
+        let bits = ch.encode_utf8(&mut bits).as_bytes();
 
-        if del_bytes > 0 {
-            self.len -= del_bytes;
+        unsafe {
+            self.insert_bytes(idx, bits);
         }
     }
 
-    /// The borrowed byte representation of the string
+    /// Inserts a string slice into this `String` at a byte position.
     ///
-    /// The opposite of this function is [`from_utf8`]
+    /// This is an `O(n)` operation as it requires copying every element in the
+    /// buffer.
     ///
-    /// [`from_utf8`]: #method.from_utf8
+    /// # Panics
+    ///
+    /// Panics if `idx` is larger than the `String`'s length, or if it does not
+    /// lie on a [`char`] boundary.
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
     ///
     /// # Examples
     ///
     /// Basic usage:
+    ///
     /// ```
     /// # extern crate small;
     /// use small::String;
-    /// let s = String::from_utf8(vec![64, 2]).unwrap();
+    /// let mut s = String::from("bar");
     ///
-    /// assert_eq!([64,2], s.as_bytes());
+    /// s.insert_str(0, "foo");
+    ///
+    /// assert_eq!("foobar", s);
     /// ```
     #[inline]
-    pub fn as_bytes(&self) -> &[u8] {
-        match self.inner {
-            Inner::Stack { ref data } => {
-                &data[..self.len]
-            },
-            Inner::Heap { data, .. } => {
-                unsafe {
-                    &::std::slice::from_raw_parts(data, self.len)
-                }
+    pub fn insert_str(&mut self, idx: usize, string: &str) {
+        assert!(self.is_char_boundary(idx));
+
+        unsafe {
+            self.insert_bytes(idx, string.as_bytes());
+        }
+    }
+
+    /// Copies `bytes` into the buffer at `idx`, shifting the tail right.
+    ///
+    /// `idx` must lie on a char boundary and `bytes` must be valid UTF-8.
+    unsafe fn insert_bytes(&mut self, idx: usize, bytes: &[u8]) {
+        use std::ptr;
+        let len = self.len;
+        let amt = bytes.len();
+        let new_len = match len.checked_add(amt) {
+            Some(x) => x,
+            None => panic!("capacity overflow")
+        };
+
+        // A stack string that spills is copied straight into its new buffer
+        // around the inserted bytes, rather than copied and then shifted
+        if let Inner::Stack { ref data } = self.inner {
+            if new_len > 23 {
+                let capacity = match new_len.checked_next_power_of_two() {
+                    Some(x) => x,
+                    None => new_len
+                };
+                let d: *mut u8 = alloc::alloc_or_abort(capacity);
+                ptr::copy_nonoverlapping(data.as_ptr(), d, idx);
+                ptr::copy_nonoverlapping(bytes.as_ptr(), d.add(idx), amt);
+                ptr::copy_nonoverlapping(data.as_ptr().add(idx), d.add(idx + amt), len - idx);
+                self.inner = Inner::Heap {
+                    capacity,
+                    data: d,
+                    align_shift: 0
+                };
+                report_growth(23, capacity);
+                self.len = new_len;
+                return;
             }
         }
+
+        self.reserve(amt);
+        ptr::copy(self.as_ptr().add(idx),
+                  self.as_mut_ptr().add(idx + amt),
+                  len - idx);
+        ptr::copy_nonoverlapping(bytes.as_ptr(),
+                                 self.as_mut_ptr().add(idx),
+                                 amt);
+        self.len = len + amt;
     }
 
-    /// The mutable byte representation of the string
+    /// Returns a raw pointer to the start of the string's buffer.
     ///
-    /// The opposite of this function is [`from_utf8`]
+    /// While the string is on the stack the pointer refers to the `String`
+    /// itself, so it is invalidated when the `String` is moved. Call
+    /// [`ensure_heap`] first if the pointer needs to survive a move.
     ///
-    /// [`from_utf8`]: #method.from_utf8
+    /// [`ensure_heap`]: #method.ensure_heap
+    #[inline(always)]
+    pub fn as_ptr(&self) -> *const u8 {
+        match &self.inner {
+            Inner::Stack { ref data } => {
+                data as *const _ as _
+            },
+            Inner::Heap { ref data, .. } => {
+                *data
+            }
+        }
+    }
+
+    /// Moves the string onto the heap if it is on the stack.
+    ///
+    /// A string on the heap keeps its buffer when the `String` is moved, so
+    /// after calling this the pointer returned by [`as_ptr`] stays valid
+    /// across moves until the string is reallocated or dropped. This is a
+    /// no-op if the string is already on the heap.
+    ///
+    /// [`as_ptr`]: #method.as_ptr
     ///
     /// # Examples
     ///
     /// Basic usage:
+    ///
     /// ```
     /// # extern crate small;
     /// use small::String;
-    /// let mut s = String::from_utf8(vec![64, 2]).unwrap();
+    /// let mut s = String::from("hello");
+    /// s.ensure_heap();
+    /// assert!(s.overflowed());
     ///
-    /// assert_eq!(&mut [64,2], unsafe { s.as_mut_bytes() });
+    /// let ptr = s.as_ptr();
+    /// let moved = s;
+    /// assert_eq!(ptr, moved.as_ptr());
     /// ```
     #[inline]
-    pub unsafe fn as_mut_bytes(&mut self) -> &mut [u8] {
+    pub fn ensure_heap(&mut self) {
+        if let Inner::Stack { data } = self.inner {
+            let d = alloc::alloc_or_abort(32);
+            unsafe {
+                ::std::ptr::copy_nonoverlapping(data.as_ptr(), d, self.len);
+            }
+            self.inner = Inner::Heap {
+                capacity: 32,
+                data: d,
+                align_shift: 0
+            };
+            report_growth(23, 32);
+        }
+    }
+
+    /// Returns a raw pointer to the start of the string's buffer.
+    ///
+    /// Unlike [`str::as_mut_ptr`], the pointer may be used to write to the
+    /// whole [`capacity`] of the buffer, not just the first [`len`] bytes.
+    /// Use [`set_len`] to declare how many bytes have been written.
+    ///
+    /// The pointer is invalidated by any operation that may move the string
+    /// or reallocate its buffer.
+    ///
+    /// [`str::as_mut_ptr`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.as_mut_ptr
+    /// [`capacity`]: #method.capacity
+    /// [`len`]: #method.len
+    /// [`set_len`]: #method.set_len
+    #[inline(always)]
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
         match &mut self.inner {
             Inner::Stack { ref mut data } => {
-                &mut data[..self.len]
+                data as *mut _ as _
             },
-            Inner::Heap { capacity: _, data } => {
-                ::std::slice::from_raw_parts_mut(*data, self.len)
+            Inner::Heap { ref data, .. } => {
+                *data
             }
         }
     }
 
-    /// This string as a [`str`]
+    /// Sets the length of the string to `new_len` bytes.
     ///
-    /// [`str`]: https://doc.rust-lang.org/nightly/std/primitive.str.html
+    /// This performs no bounds or UTF-8 checks. It is intended for use after
+    /// writing into the buffer through [`as_mut_ptr`].
+    ///
+    /// [`as_mut_ptr`]: #method.as_mut_ptr
+    ///
+    /// # Safety
+    ///
+    /// `new_len` must be less than or equal to [`capacity`], and the bytes
+    /// `[0..new_len]` must be initialized and valid UTF-8.
+    ///
+    /// [`capacity`]: #method.capacity
     ///
     /// # Examples
     ///
@@ -505,18 +942,37 @@ impl String {
     /// ```
     /// # extern crate small;
     /// use small::String;
-    /// let s = String::from("Hello!");
+    /// let mut s = String::new();
     ///
-    /// assert_eq!("Hello!", s);
+    /// unsafe {
+    ///     let ptr = s.as_mut_ptr();
+    ///     for (i, digit) in b"123".iter().enumerate() {
+    ///         *ptr.add(i) = *digit;
+    ///     }
+    ///     s.set_len(3);
+    /// }
+    ///
+    /// assert_eq!("123", s);
     /// ```
     #[inline]
-    pub fn as_str(&self) -> &str {
-        self
+    pub unsafe fn set_len(&mut self, new_len: usize) {
+        self.len = new_len;
     }
 
-    /// Push a [`str`] onto the end of the string
+    /// Returns the unused part of the string's buffer, from its length up to
+    /// its capacity.
     ///
-    /// [`str`]: https://doc.rust-lang.org/nightly/std/primitive.str.html
+    /// A full string on the stack is first moved to the heap so that there is
+    /// room to write into. After writing, use [`set_len`] to include the
+    /// written bytes in the string.
+    ///
+    /// [`set_len`]: #method.set_len
+    ///
+    /// # Safety
+    ///
+    /// The returned bytes may be uninitialized, so they must be written to
+    /// before they are read. Any bytes later included in the string with
+    /// [`set_len`] must be valid UTF-8.
     ///
     /// # Examples
     ///
@@ -524,65 +980,31 @@ impl String {
     /// ```
     /// # extern crate small;
     /// use small::String;
-    /// let mut s = String::from("Hello");
-    /// s.push_str(" World!");
+    /// let mut s = String::from("answer: ");
     ///
-    /// assert_eq!("Hello World!", s);
+    /// unsafe {
+    ///     let spare = s.spare_capacity_mut();
+    ///     spare[..2].copy_from_slice(b"42");
+    ///     let len = s.len();
+    ///     s.set_len(len + 2);
+    /// }
+    ///
+    /// assert_eq!("answer: 42", s);
     /// ```
     #[inline]
-    pub fn push_str(&mut self, item: &str) {
-        let new_len = self.len + item.len();
-        // we match &mut self.inner so we don't copy the byte array
-        match (&mut self.inner, self.len + item.len()) {
-            (Inner::Stack { data }, 0...23) => {
-                // Due to a compiler bug, [x..x+y] is more efficient than [x..][..y]
-                data[self.len..new_len].copy_from_slice(item.as_bytes());
-            },
-            (Inner::Heap { ref mut capacity, ref mut data }, x) => {
-                if x > *capacity {
-                    let new_len = match new_len.checked_next_power_of_two() {
-                        Some(x) => x,
-                        None => new_len
-                    };
-                    Self::grow(capacity, data, new_len);
-                }
-                unsafe {
-                    ::std::ptr::copy_nonoverlapping(item.as_ptr(), data.add(self.len), item.len())
-                }
-            },
-            stack @ (Inner::Stack { .. } , _) => {
-                let capacity = match new_len.checked_next_power_of_two() {
-                    Some(x) => x,
-                    None => new_len
-                };
-                let d = if let Inner::Stack { ref data } = stack.0 {
-                    let d = alloc::alloc(capacity);
-                    unsafe {
-                        ::std::ptr::copy_nonoverlapping(data.as_ptr(), d, self.len);
-                        ::std::ptr::copy_nonoverlapping(item.as_ptr(), d.add(self.len), item.len());
-                    }
-                    d
-                } else {
-                    //
-                    // We know from the match above that `stack.0` is definitely `Inner::Stack`.
-                    // Therefore we should never reach this location.
-                    //
-                    unsafe { unreachable_unchecked() }
-                };
-                *stack.0 = Inner::Heap {
-                    capacity: capacity,
-                    data: d
-                };
-            }
+    pub unsafe fn spare_capacity_mut(&mut self) -> &mut [u8] {
+        if self.len == 23 {
+            self.ensure_heap();
         }
-        self.len = new_len;
+        let (len, capacity) = (self.len, self.capacity());
+        ::std::slice::from_raw_parts_mut(self.as_mut_ptr().add(len), capacity - len)
     }
 
-    /// Push a character onto the end of the string
-    ///
-    /// The opposite of this function is [`pop`]
+    /// Retains only the characters specified by the predicate.
     ///
-    /// [`pop`]: #method.pop
+    /// In other words, remove all characters `c` such that `f(c)` returns `false`.
+    /// This method operates in place and preserves the order of the retained
+    /// characters.
     ///
     /// # Examples
     ///
@@ -590,249 +1012,240 @@ impl String {
     /// ```
     /// # extern crate small;
     /// use small::String;
-    /// let mut s = String::from("Hello");
-    /// s.push('!');
+    /// let mut s = String::from("f_o_ob_ar");
     ///
-    /// assert_eq!("Hello!", s)
+    /// s.retain(|c| c != '_');
+    ///
+    /// assert_eq!(s, "foobar");
     /// ```
     #[inline]
-    pub fn push(&mut self, item: char) {
-        let ch_len = item.len_utf8();
-        let mut chs = [0; 4];
-        item.encode_utf8(&mut chs);
-        let new_len = self.len + ch_len;
-        // we match &mut self.inner so we don't copy the byte array
-        match (&mut self.inner, self.len + ch_len) {
-            (Inner::Stack { data }, 0...23) => {
-                data[self.len..new_len].copy_from_slice(&chs[..ch_len]);
-            },
-            (Inner::Heap { ref mut capacity, ref mut data }, x) => {
-                if x > *capacity {
-                    // This is correct as long as capacity != 0
-                    let new_capacity = *capacity*2;
-                    Self::grow(capacity, data, new_capacity);
-                }
+    pub fn retain<F>(&mut self, mut f: F)
+        where F: FnMut(char) -> bool
+    {
+        use std::ptr;
+        let mut del_bytes = 0;
+        let mut idx = 0;
+
+        while idx < self.len {
+            let ch = unsafe {
+                self.get_unchecked(idx..self.len).chars().next().unwrap()
+            };
+            let ch_len = ch.len_utf8();
+
+            if !f(ch) {
+                del_bytes += ch_len;
+            } else if del_bytes > 0 {
                 unsafe {
-                    ::std::ptr::copy_nonoverlapping(chs.as_ptr(), data.add(self.len), ch_len)
+                    ptr::copy(self.as_ptr().offset(idx as isize),
+                              self.as_mut_ptr().offset((idx - del_bytes) as isize),
+                              ch_len);
                 }
-            },
-            stack @ (Inner::Stack { .. }, _) => {
-                let d = if let Inner::Stack { ref data } = stack.0 {
-                    let d = alloc::alloc(32);
-                    unsafe {
-                        ::std::ptr::copy_nonoverlapping(data.as_ptr(), d, self.len);
-                        ::std::ptr::copy_nonoverlapping(chs.as_ptr(), d.add(self.len), ch_len);
-                    }
-                    d
-                } else {
-                    //
-                    // We know from the match above that `stack.0` is definitely `Inner::Stack`.
-                    // Therefore we should never reach this location.
-                    //
-                    unsafe { unreachable_unchecked() }
-                };
-                *stack.0 = Inner::Heap {
-                    capacity: 32,
-                    data: d
-                };
             }
+
+            // Point idx to the next char
+            idx += ch_len;
+        }
+
+        if del_bytes > 0 {
+            self.len -= del_bytes;
         }
-        self.len = new_len;
     }
 
-    /// Converts a vector of bytes to a `String`.
-    ///
-    /// A string slice ([`&str`]) is made of bytes ([`u8`]), and a vector of bytes
-    /// ([`Vec<u8>`]) is made of bytes, so this function converts between the
-    /// two. Not all byte slices are valid `String`s, however: `String`
-    /// requires that it is valid UTF-8. `from_utf8()` checks to ensure that
-    /// the bytes are valid UTF-8, and then does the conversion.
-    ///
-    /// If you are sure that the byte slice is valid UTF-8, and you don't want
-    /// to incur the overhead of the validity check, there is an unsafe version
-    /// of this function, [`from_utf8_unchecked`], which has the same behavior
-    /// but skips the check.
-    ///
-    /// This method will take care to not copy the vector, for efficiency's
-    /// sake.
-    ///
-    /// If you need a [`&str`] instead of a `String`, consider
-    /// [`str::from_utf8`].
-    ///
-    /// The inverse of this method is [`as_bytes`].
+    /// Retains only the ASCII bytes specified by the predicate.
     ///
-    /// # Errors
+    /// This works like [`retain`], but hands the predicate bytes instead of
+    /// decoded [`char`]s, which is faster for ASCII cleanup. To keep the
+    /// string valid UTF-8 the predicate is only called for ASCII bytes, and
+    /// every byte of a non-ASCII character is always kept.
     ///
-    /// Returns [`Err`] if the slice is not UTF-8 with a description as to why the
-    /// provided bytes are not UTF-8. The vector you moved in is also included.
+    /// [`retain`]: #method.retain
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
     ///
     /// # Examples
     ///
     /// Basic usage:
-    ///
     /// ```
     /// # extern crate small;
     /// use small::String;
-    /// // some bytes, in a vector
-    /// let sparkle_heart = vec![240, 159, 146, 150];
+    /// let mut s = String::from("a, b; c€!");
     ///
-    /// // We know these bytes are valid, so we'll use `unwrap()`.
-    /// let sparkle_heart = String::from_utf8(sparkle_heart).unwrap();
+    /// s.retain_bytes(|b| b.is_ascii_alphanumeric());
     ///
-    /// assert_eq!("💖", sparkle_heart);
+    /// assert_eq!(s, "abc€");
     /// ```
+    pub fn retain_bytes<F>(&mut self, mut f: F)
+        where F: FnMut(u8) -> bool
+    {
+        let mut del_bytes = 0;
+        // Only ASCII bytes can be removed, so the result stays valid UTF-8
+        let bytes = unsafe { self.as_mut_bytes() };
+        for idx in 0..bytes.len() {
+            let b = bytes[idx];
+            if b.is_ascii() && !f(b) {
+                del_bytes += 1;
+            } else if del_bytes > 0 {
+                bytes[idx - del_bytes] = b;
+            }
+        }
+        self.len -= del_bytes;
+    }
+
+    /// Retains only the characters specified by the predicate, moving the
+    /// string back onto the stack if the result fits in 23 bytes.
     ///
-    /// Incorrect bytes:
+    /// This behaves exactly like [`retain`], except that a heap allocated
+    /// string whose retained characters fit on the stack has its heap buffer
+    /// freed.
+    ///
+    /// [`retain`]: #method.retain
     ///
+    /// # Examples
+    ///
+    /// Basic usage:
     /// ```
     /// # extern crate small;
     /// use small::String;
-    /// // some invalid bytes, in a vector
-    /// let sparkle_heart = vec![0, 159, 146, 150];
-    ///
-    /// assert!(String::from_utf8(sparkle_heart).is_err());
-    /// ```
+    /// let mut s = String::from("a_b_c_d_e_f_g_h_i_j_k_l_m");
+    /// assert!(s.overflowed());
     ///
-    /// See the docs for [`FromUtf8Error`] for more details on what you can do
-    /// with this error.
+    /// s.retain_and_compact(|c| c != '_');
     ///
-    /// [`from_utf8_unchecked`]: struct.String.html#method.from_utf8_unchecked
-    /// [`&str`]: https://doc.rust-lang.org/nightly/std/primitive.str.html
-    /// [`u8`]: https://doc.rust-lang.org/nightly/std/primitive.u8.html
-    /// [`Vec<u8>`]: https://doc.rust-lang.org/nightly/std/vec/struct.Vec.html
-    /// [`str::from_utf8`]: https://doc.rust-lang.org/nightly/std/str/fn.from_utf8.html
-    /// [`as_bytes`]: struct.String.html#method.as_bytes
-    /// [`FromUtf8Error`]: struct.FromUtf8Error.html
-    /// [`Err`]: https://doc.rust-lang.org/nightly/std/result/enum.Result.html#variant.Err
+    /// assert_eq!(s, "abcdefghijklm");
+    /// assert!(!s.overflowed());
+    /// ```
     #[inline]
-    pub fn from_utf8(vec: std::vec::Vec<u8>) -> Result<String, FromUtf8Error> {
-        use std::str;
-        match str::from_utf8(&vec) {
-            Ok(..) => {
-                let boxed = vec.into_boxed_slice();
-                let (capacity, len, data) = (boxed.len(), boxed.len(), Box::into_raw(boxed) as _);
-                Ok(String {
-                    len,
-                    inner: Inner::Heap {
-                        capacity,
-                        data
-                    }
-                })
-            },
-            Err(e) => {
-                Err(FromUtf8Error {
-                    bytes: vec,
-                    error: e
-                })
-            }
-        }
+    pub fn retain_and_compact<F>(&mut self, f: F)
+        where F: FnMut(char) -> bool
+    {
+        self.retain(f);
+        self.move_to_stack();
     }
 
-    /// Converts a vector of bytes to a `String` without checking that the
-    /// string contains valid UTF-8.
-    ///
-    /// See the safe version, [`from_utf8`], for more details.
-    ///
-    /// [`from_utf8`]: struct.String.html#method.from_utf8
+    /// Retains only the characters specified by the predicate, moving the
+    /// string back onto the stack if the result fits in 23 bytes, and reports
+    /// what changed.
     ///
-    /// # Safety
+    /// This behaves exactly like [`retain_and_compact`], but returns a tuple
+    /// of the number of characters removed and whether the heap buffer was
+    /// freed as a result.
     ///
-    /// This function is unsafe because it does not check that the bytes passed
-    /// to it are valid UTF-8. If this constraint is violated, it may cause
-    /// memory unsafety issues with future users of the `String`, as the rest of
-    /// the standard library assumes that `String`s are valid UTF-8.
+    /// [`retain_and_compact`]: #method.retain_and_compact
     ///
     /// # Examples
     ///
     /// Basic usage:
-    ///
     /// ```
     /// # extern crate small;
     /// use small::String;
-    /// // some bytes, in a vector
-    /// let sparkle_heart = vec![240, 159, 146, 150];
+    /// let mut s = String::from("a_b_c_d_e_f_g_h_i_j_k_l_m");
     ///
-    /// let sparkle_heart = unsafe {
-    ///     String::from_utf8_unchecked(sparkle_heart)
-    /// };
+    /// assert_eq!(s.retain_reporting(|c| c != '_'), (12, true));
+    /// assert_eq!(s, "abcdefghijklm");
     ///
-    /// assert_eq!("💖", sparkle_heart);
+    /// assert_eq!(s.retain_reporting(|c| c != 'a'), (1, false));
+    /// assert_eq!(s, "bcdefghijklm");
     /// ```
+    pub fn retain_reporting<F>(&mut self, mut f: F) -> (usize, bool)
+        where F: FnMut(char) -> bool
+    {
+        let was_heap = self.overflowed();
+        let mut removed = 0;
+        self.retain_and_compact(|c| {
+            let keep = f(c);
+            if !keep {
+                removed += 1;
+            }
+            keep
+        });
+        (removed, was_heap && !self.overflowed())
+    }
+
+    /// Moves a heap allocated string back onto the stack, freeing its buffer,
+    /// if its contents fit in 23 bytes.
     #[inline]
-    pub unsafe fn from_utf8_unchecked(mut vec: std::vec::Vec<u8>) -> String {
-        let (capacity, data, len) = (vec.capacity(), vec.as_mut_ptr(), vec.len());
-        let s = String {
-            len,
-            inner: Inner::Heap {
-                capacity,
-                data
+    fn move_to_stack(&mut self) {
+        if let Inner::Heap { capacity, data, align_shift } = self.inner {
+            if self.len <= 23 {
+                let mut d = [0u8;23];
+                d[..self.len].copy_from_slice(unsafe {
+                    ::std::slice::from_raw_parts(data, self.len)
+                });
+                if capacity > 0 {
+                    unsafe { Self::dealloc_heap(data, capacity, align_shift) };
+                }
+                self.inner = Inner::Stack { data: d };
             }
-        };
-        ::std::mem::forget(vec);
-        s
+        }
     }
 
-    /// Returns the bytes that were attempted to convert to a `String`.
+    /// Returns a copy of this string containing only the characters specified
+    /// by the predicate.
     ///
-    /// This method is carefully constructed to avoid allocation. It will
-    /// consume the error, moving out the bytes, so that a copy of the bytes
-    /// does not need to be made.
+    /// This is the non-mutating counterpart to [`retain`]: the kept characters
+    /// are written into a fresh `String` rather than shifted in place, and the
+    /// original string is left untouched.
+    ///
+    /// [`retain`]: #method.retain
     ///
     /// # Examples
     ///
     /// Basic usage:
-    ///
     /// ```
     /// # extern crate small;
     /// use small::String;
-    /// // some invalid bytes, in a vector
-    /// let bytes = vec![0, 159];
+    /// let s = String::from("f_o_ob_ar");
     ///
-    /// let value = String::from_utf8(bytes);
+    /// let t = s.retain_into(|c| c != '_');
     ///
-    /// assert_eq!(vec![0, 159], value.unwrap_err().into_bytes());
+    /// assert_eq!(t, "foobar");
+    /// assert_eq!(s, "f_o_ob_ar");
     /// ```
     #[inline]
-    pub fn into_bytes(self) -> std::vec::Vec<u8> {
-        let v = match &self.inner {
-            Inner::Stack { ref data } => {
-                let mut v = ::std::vec::Vec::new();
-                v.extend_from_slice(data);
-                v
-            },
-            Inner::Heap { ref capacity, ref data } => {
-                unsafe {
-                    ::std::vec::Vec::from_raw_parts(*data, self.len, *capacity)
-                }
-            }
-        };
-        ::std::mem::forget(self);
-        v
+    pub fn retain_into<F>(&self, mut f: F) -> String
+        where F: FnMut(char) -> bool
+    {
+        self.chars().filter(|&ch| f(ch)).collect()
     }
 
-    /// Converts a `String` into a mutable string slice.
+    /// Replaces all occurrences of the [`char`] `from` with `to`, borrowing
+    /// the string unchanged if `from` does not occur.
+    ///
+    /// Unlike [`str::replace`], this does not allocate when there is nothing
+    /// to replace.
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    /// [`str::replace`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.replace
     ///
     /// # Examples
     ///
     /// Basic usage:
-    ///
     /// ```
     /// # extern crate small;
     /// use small::String;
-    /// let mut s = String::from("foobar");
-    /// let s_mut_str = s.as_mut_str();
+    /// use std::borrow::Cow;
     ///
-    /// s_mut_str.make_ascii_uppercase();
+    /// let s = String::from("a-b-c");
     ///
-    /// assert_eq!("FOOBAR", s_mut_str);
+    /// assert_eq!(Cow::Borrowed("a-b-c"), s.replace_cow('_', ' '));
+    /// assert_eq!("a b c", s.replace_cow('-', ' '));
     /// ```
     #[inline]
-    pub fn as_mut_str(&mut self) -> &mut str {
-        self
+    pub fn replace_cow(&self, from: char, to: char) -> Cow<'_, str> {
+        if !self.contains(from) {
+            return Cow::Borrowed(self);
+        }
+        let mut buf = [0; 4];
+        Cow::Owned(self.as_str().replace(from, to.encode_utf8(&mut buf)))
     }
 
-    /// Shrinks the capacity of the string to be the same as the length of their
-    /// string. While allocated on the stack, this is a no-op
+    /// Splits the string by whitespace, yielding each token as an owned
+    /// `String`.
+    ///
+    /// This behaves like [`str::split_whitespace`]: runs of whitespace are
+    /// collapsed and leading or trailing whitespace produces no empty tokens.
+    /// Tokens of 23 bytes or less are stored on the stack.
+    ///
+    /// [`str::split_whitespace`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.split_whitespace
     ///
     /// # Examples
     ///
@@ -840,125 +1253,134 @@ impl String {
     /// ```
     /// # extern crate small;
     /// use small::String;
+    /// let s = String::from("  a  bb  ");
+    /// let mut tokens = s.split_whitespace_owned();
     ///
-    /// // On the stack
-    /// let mut s = String::from("Hello!");
-    /// assert_eq!(23, s.capacity());
-    /// s.shrink_to_fit();
-    /// assert_eq!(23, s.capacity());
-    ///
-    /// // On the heap
-    /// let mut s = String::from("abcdefghijklmnopqrstuvwxyz");
-    /// assert_eq!(32, s.capacity());
-    /// s.shrink_to_fit();
-    /// assert_eq!(26, s.capacity());
+    /// assert_eq!(Some(String::from("a")), tokens.next());
+    /// assert_eq!(Some(String::from("bb")), tokens.next());
+    /// assert_eq!(None, tokens.next());
     /// ```
     #[inline]
-    pub fn shrink_to_fit(&mut self) {
-        if let Inner::Heap { ref mut capacity, ref mut data } = &mut self.inner {
-            *data = unsafe { alloc::realloc(*data, *capacity, self.len) };
-            *capacity = self.len;
-        }
+    pub fn split_whitespace_owned(&self) -> impl Iterator<Item = String> + '_ {
+        self.split_whitespace().map(String::from)
     }
 
-    /// Ensures that this `String`'s capacity is at least `additional` bytes
-    /// larger than its length.
-    ///
-    /// The capacity may be increased by more than `additional` bytes if it
-    /// chooses, to prevent frequent reallocations.
+    /// Returns an iterator over the occurrences of the [`char`] `pat` in the
+    /// string, each as an owned `String`.
     ///
-    /// # Panics
-    ///
-    /// Panics if the new capacity overflows [`usize`].
+    /// This behaves like [`str::matches`], except that the matches can
+    /// outlive the string they were found in.
     ///
-    /// [`usize`]: ../../std/primitive.usize.html
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    /// [`str::matches`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.matches
     ///
     /// # Examples
     ///
     /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("banana");
+    /// let matches: Vec<String> = s.matches_owned('a').collect();
     ///
+    /// assert_eq!(vec!["a", "a", "a"], matches);
     /// ```
-    /// let mut s = String::new();
+    #[inline]
+    pub fn matches_owned(&self, pat: char) -> impl Iterator<Item = String> + '_ {
+        self.matches(pat).map(String::from)
+    }
+
+    /// Returns an iterator over the non-overlapping occurrences of `pat` in
+    /// the string, each as an owned `String`.
     ///
-    /// s.reserve(10);
+    /// This is the [`str`] counterpart of [`matches_owned`].
     ///
-    /// assert!(s.capacity() >= 10);
-    /// ```
+    /// [`str`]: https://doc.rust-lang.org/nightly/std/primitive.str.html
+    /// [`matches_owned`]: #method.matches_owned
     ///
-    /// This may not actually increase the capacity:
+    /// # Examples
     ///
+    /// Basic usage:
     /// ```
     /// # extern crate small;
     /// use small::String;
+    /// let s = String::from("banana");
+    /// let matches: Vec<String> = s.matches_str_owned("an").collect();
     ///
-    /// // Lets create a string on the heap
-    /// let mut s = String::with_capacity(10);
-    /// s.push('a');
-    /// s.push('b');
+    /// assert_eq!(vec!["an", "an"], matches);
+    /// ```
+    #[inline]
+    pub fn matches_str_owned<'a>(&'a self, pat: &'a str) -> impl Iterator<Item = String> + 'a {
+        self.matches(pat).map(String::from)
+    }
+
+    /// Replaces all matches of the string slice `from` with `to`.
     ///
-    /// // s now has a length of 2 and a capacity of 10
-    /// assert_eq!(2, s.len());
-    /// assert_eq!(10, s.capacity());
+    /// Matches are found from left to right and do not overlap, as with
+    /// [`str::replace`]. Unlike it, this returns a `small::String`, so short
+    /// results are stored on the stack. [`str::replace`] is still available
+    /// for other pattern types, such as a `char` or a closure.
     ///
-    /// // Since we already have an extra 8 capacity, calling this...
-    /// s.reserve(8);
+    /// [`str::replace`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.replace
     ///
-    /// // ... doesn't actually increase.
-    /// assert_eq!(10, s.capacity());
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("this is old");
+    ///
+    /// assert_eq!("this is new", s.replace_owned("old", "new"));
+    /// assert_eq!("than an old", s.replace_owned("is", "an"));
     /// ```
     #[inline]
-    pub fn reserve(&mut self, additional: usize) {
-        let new_cap = self.len + additional;
-        // we match &mut self.inner so we don't copy the byte array
-        match (&mut self.inner, self.len + additional) {
-            (Inner::Stack { data: _ }, 0...23) => {},
-            (Inner::Heap { ref mut capacity, ref mut data }, x) => {
-                if x > *capacity {
-                    let new_len = match new_cap.checked_next_power_of_two() {
-                        Some(x) => x,
-                        None => new_cap
-                    };
-                    Self::grow(capacity, data, new_len);
-                }
-            },
-            stack @ (Inner::Stack { .. }, _) => {
-                let new_len = match new_cap.checked_next_power_of_two() {
-                    Some(x) => x,
-                    None => new_cap
-                };
-                let d = if let Inner::Stack { ref data } = stack.0 {
-                    let d = alloc::alloc(new_len);
-                    unsafe {
-                        ::std::ptr::copy_nonoverlapping(data.as_ptr(), d, self.len);
-                    }
-                    d
-                } else {
-                    //
-                    // We know from the match above that `stack.0` is definitely `Inner::Stack`.
-                    // Therefore we should never reach this location.
-                    //
-                    unsafe { unreachable_unchecked() }
-                };
-                *stack.0 = Inner::Heap {
-                    capacity: new_len,
-                    data: d
-                };
-            }
-        }
+    pub fn replace_owned(&self, from: &str, to: &str) -> String {
+        self.replacen_owned(from, to, usize::MAX)
     }
 
-    #[inline]
-    fn grow(capacity: &mut usize, data: &mut *mut u8, new_cap: usize) {
-        let d = unsafe { alloc::realloc(*data, *capacity, new_cap) };
-        if d.is_null() {
-            panic!("OOM")
+    /// Replaces the first `count` matches of the string slice `from` with
+    /// `to`.
+    ///
+    /// Matches are found from left to right and do not overlap, as with
+    /// [`str::replacen`]. Unlike it, this returns a `small::String`, so short
+    /// results are stored on the stack. [`str::replacen`] is still available
+    /// for other pattern types, such as a `char` or a closure.
+    ///
+    /// [`str::replacen`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.replacen
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("foo foo 123 foo");
+    ///
+    /// assert_eq!("new new 123 foo", s.replacen_owned("foo", "new", 2));
+    /// ```
+    pub fn replacen_owned(&self, from: &str, to: &str, count: usize) -> String {
+        let mut result = String::new();
+        let mut last_end = 0;
+        for (start, part) in self.match_indices(from).take(count) {
+            result.push_str(&self[last_end..start]);
+            result.push_str(to);
+            last_end = start + part.len();
         }
-        *data = d;
-        *capacity = new_cap;
+        result.push_str(&self[last_end..]);
+        result
     }
 
-    /// Clears the string. This performs no deallocation, so any string on the
-    /// heap will remain allocated on the heap.
+    /// Creates a new `String` by repeating this string `n` times.
+    ///
+    /// Unlike [`str::repeat`], this returns a `small::String`, so short
+    /// results are stored on the stack.
+    ///
+    /// [`str::repeat`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.repeat
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of the result would overflow.
     ///
     /// # Examples
     ///
@@ -966,779 +1388,4943 @@ impl String {
     /// ```
     /// # extern crate small;
     /// use small::String;
+    /// let s = String::from("abc");
     ///
-    /// let mut s = String::from("Hello!");
-    /// assert_eq!(6, s.len());
-    /// assert_eq!("Hello!", s);
-    ///
-    /// s.clear();
-    /// assert_eq!(0, s.len());
-    /// assert_eq!("", s);
+    /// assert_eq!("abcabcabcabc", s.repeat(4));
     /// ```
-    #[inline]
-    pub fn clear(&mut self) {
-        self.truncate(0);
+    pub fn repeat(&self, n: usize) -> String {
+        use std::ptr;
+        let capacity = match self.len.checked_mul(n) {
+            Some(x) => x,
+            None => panic!("capacity overflow")
+        };
+        let mut buf = String::new();
+        if capacity == 0 {
+            return buf;
+        }
+        buf.reserve_exact(capacity);
+        buf.push_str(self);
+
+        // Double the copied prefix until it no longer fits, then copy what
+        // is left of the last repetition
+        let mut len = self.len;
+        unsafe {
+            let data = buf.as_mut_ptr();
+            while len <= capacity - len {
+                ptr::copy_nonoverlapping(data, data.add(len), len);
+                len *= 2;
+            }
+            ptr::copy_nonoverlapping(data, data.add(len), capacity - len);
+        }
+        buf.len = capacity;
+        buf
     }
-}
 
-impl AsRef<str> for String {
+    /// Returns a new `String` with all leading and trailing occurrences of the
+    /// [`char`] `c` removed.
+    ///
+    /// Use [`str::trim_matches`] through `Deref` for a borrowed result.
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    /// [`str::trim_matches`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.trim_matches
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("xxhixx");
+    ///
+    /// assert_eq!("hi", s.trim_matches_char_owned('x'));
+    /// assert_eq!("xxhixx", s);
+    /// ```
     #[inline]
-    fn as_ref(&self) -> &str {
-        self
+    pub fn trim_matches_char_owned(&self, c: char) -> String {
+        String::from(self.trim_matches(c))
     }
-}
 
-impl AsRef<[u8]> for String {
+    /// Returns a new `String` with all leading occurrences of the string
+    /// slice `pat` removed.
+    ///
+    /// Use [`str::trim_start_matches`] through `Deref` for a borrowed result.
+    ///
+    /// [`str::trim_start_matches`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.trim_start_matches
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("///a///");
+    ///
+    /// assert_eq!("a///", s.trim_start_matches_owned("/"));
+    /// assert_eq!("/a///", s.trim_start_matches_owned("//"));
+    /// ```
     #[inline]
-    fn as_ref(&self) -> &[u8] {
-        self.as_bytes()
+    pub fn trim_start_matches_owned(&self, pat: &str) -> String {
+        String::from(self.trim_start_matches(pat))
     }
-}
 
-impl Default for String {
+    /// Returns a new `String` with all trailing occurrences of the string
+    /// slice `pat` removed.
+    ///
+    /// Use [`str::trim_end_matches`] through `Deref` for a borrowed result.
+    ///
+    /// [`str::trim_end_matches`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.trim_end_matches
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("a///");
+    ///
+    /// assert_eq!("a", s.trim_end_matches_owned("/"));
+    /// assert_eq!("a/", s.trim_end_matches_owned("//"));
+    /// ```
     #[inline]
-    fn default() -> String {
-        String::new()
+    pub fn trim_end_matches_owned(&self, pat: &str) -> String {
+        String::from(self.trim_end_matches(pat))
     }
-}
 
-impl Borrow<str> for String {
-    #[inline]
-    fn borrow(&self) -> &str {
-        self
+    /// Splits the string into owned frames of at most `max` bytes each,
+    /// without splitting a [`char`].
+    ///
+    /// Frames are filled greedily. A single character longer than `max` bytes
+    /// is placed in a frame of its own.
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("aaa€");
+    ///
+    /// assert_eq!(s.frame_by_bytes(3), vec!["aaa", "€"]);
+    /// assert_eq!(s.frame_by_bytes(2), vec!["aa", "a", "€"]);
+    /// ```
+    pub fn frame_by_bytes(&self, max: usize) -> std::vec::Vec<String> {
+        let mut frames = std::vec::Vec::new();
+        let mut start = 0;
+        for (idx, c) in self.char_indices() {
+            if idx > start && idx + c.len_utf8() - start > max {
+                frames.push(String::from(&self[start..idx]));
+                start = idx;
+            }
+        }
+        if start < self.len {
+            frames.push(String::from(&self[start..]));
+        }
+        frames
     }
-}
 
-impl ::std::ops::Deref for String {
-    type Target = str;
-    #[inline]
-    fn deref(&self) -> &str {
-        match self.inner {
-            Inner::Stack { ref data } => {
-                unsafe {
-                    ::std::str::from_utf8_unchecked(&data[..self.len])
-                }
-            }
-            _ => {
-                unsafe {
-                    ::std::str::from_utf8_unchecked(self.as_bytes())
-                }
+    /// Appends the contents of this string to `dest`, escaped for use inside
+    /// a JSON string literal.
+    ///
+    /// Quotes and backslashes are escaped with a backslash, and control
+    /// characters are written as `\uXXXX` unless they have a short escape
+    /// such as `\n`. No surrounding quotes are added.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("a\"b\n");
+    /// let mut json = String::from("{\"key\":\"");
+    ///
+    /// s.push_json_escaped(&mut json);
+    /// json.push_str("\"}");
+    ///
+    /// assert_eq!(r#"{"key":"a\"b\n"}"#, json);
+    /// ```
+    pub fn push_json_escaped(&self, dest: &mut String) {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        let bytes = self.as_bytes();
+        let mut start = 0;
+        for (i, &b) in bytes.iter().enumerate() {
+            let escape = match b {
+                b'"' => b'"',
+                b'\\' => b'\\',
+                b'\n' => b'n',
+                b'\r' => b'r',
+                b'\t' => b't',
+                0x08 => b'b',
+                0x0c => b'f',
+                0x00..=0x1f => b'u',
+                _ => continue
+            };
+            dest.push_str(&self[start..i]);
+            start = i + 1;
+            if escape == b'u' {
+                let code = [
+                    b'\\', b'u', b'0', b'0',
+                    HEX[(b >> 4) as usize], HEX[(b & 0xf) as usize]
+                ];
+                dest.push_str(unsafe { ::std::str::from_utf8_unchecked(&code) });
+            } else {
+                let code = [b'\\', escape];
+                dest.push_str(unsafe { ::std::str::from_utf8_unchecked(&code) });
             }
         }
+        dest.push_str(&self[start..]);
     }
-}
 
-impl ::std::ops::DerefMut for String {
+    /// Returns a [`Display`] adapter that writes at most `max_chars`
+    /// characters of the string, followed by `…` if any were cut off.
+    ///
+    /// Width, fill and alignment are honoured. No new string is allocated,
+    /// unless one of those is given and the truncated text does not fit on
+    /// the stack.
+    ///
+    /// [`Display`]: https://doc.rust-lang.org/nightly/std/fmt/trait.Display.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("abcdef");
+    ///
+    /// assert_eq!("abc…", format!("{}", s.display_truncated(3)));
+    /// assert_eq!("abcdef", format!("{}", s.display_truncated(6)));
+    /// assert_eq!("  abc…", format!("{:>6}", s.display_truncated(3)));
+    /// ```
     #[inline]
-    fn deref_mut(&mut self) -> &mut str {
-        match self.inner {
-            Inner::Stack { ref mut data } => {
-                unsafe {
-                    ::std::str::from_utf8_unchecked_mut(&mut data[..self.len])
-                }
-            }
-            _ => {
-                unsafe {
-                    ::std::str::from_utf8_unchecked_mut(self.as_mut_bytes())
-                }
-            }
+    pub fn display_truncated(&self, max_chars: usize) -> impl fmt::Display + '_ {
+        Truncated {
+            s: self,
+            max_chars
         }
     }
-}
 
-impl Clone for String {
+    /// An iterator over the lines of the string, along with the byte offset
+    /// at which each line starts.
+    ///
+    /// Lines are split as in [`str::lines`], so a trailing `\r` is removed
+    /// from each line. The offsets are useful for mapping a line back to its
+    /// position in the source, for example when reporting errors.
+    ///
+    /// [`str::lines`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.lines
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("ab\ncd");
+    /// let mut lines = s.line_offsets();
+    ///
+    /// assert_eq!(Some((0, "ab")), lines.next());
+    /// assert_eq!(Some((3, "cd")), lines.next());
+    /// assert_eq!(None, lines.next());
+    /// ```
     #[inline]
-    fn clone(&self) -> Self {
-        String {
-            len: self.len,
-            inner: match (self.inner, self.len) {
-                stack @ (Inner::Stack { .. }, _) => stack.0,
-                (Inner::Heap { data, .. }, 0...23) => {
-                    Inner::Stack {
-                        data: {
-                            let mut d = [0u8;23];
-                            d[..self.len].copy_from_slice(
-                                unsafe {
-                                    ::std::slice::from_raw_parts(data, self.len)
-                                });
-                            d
-                        }
-                    }
-                },
-                (Inner::Heap { capacity, data }, _) => {
-                    use std::ptr;
-                    Inner::Heap {
-                        capacity,
-                        data: {
-                            let d = alloc::alloc(capacity);
-                            unsafe {
-                                ptr::copy_nonoverlapping(data, d, self.len);
-                            }
-                            d
-                        }
-                    }
-                }
-            }
+    pub fn line_offsets(&self) -> impl Iterator<Item = (usize, &str)> + '_ {
+        let start = self.as_ptr() as usize;
+        self.lines().map(move |line| (line.as_ptr() as usize - start, line))
+    }
+
+    /// The borrowed byte representation of the string
+    ///
+    /// The opposite of this function is [`from_utf8`]
+    ///
+    /// [`from_utf8`]: #method.from_utf8
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from_utf8(vec![64, 2]).unwrap();
+    ///
+    /// assert_eq!([64,2], s.as_bytes());
+    /// ```
+    #[inline(always)]
+    pub fn as_bytes(&self) -> &[u8] {
+        // Selecting the pointer and building the slice from it avoids the
+        // branch and bounds check of slicing the stack array
+        unsafe {
+            ::std::slice::from_raw_parts(self.as_ptr(), self.len)
         }
     }
-}
 
-impl std::hash::Hash for String {
+    /// Checks that the byte at position `index` is the first byte of a UTF-8
+    /// sequence or the end of the string.
+    ///
+    /// The start and end of the string are considered to be boundaries.
+    /// Returns false if `index` is greater than the length of the string.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("a€b");
+    ///
+    /// assert!(s.is_char_boundary(1));
+    /// assert!(!s.is_char_boundary(2));
+    /// assert!(s.is_char_boundary(5));
+    /// ```
     #[inline]
-    fn hash<H: std::hash::Hasher>(&self, hs: &mut H) {
-        (**self).hash(hs)
+    pub fn is_char_boundary(&self, index: usize) -> bool {
+        self.as_str().is_char_boundary(index)
     }
-}
-
-impl std::ops::Index<std::ops::Range<usize>> for String {
-    type Output = str;
 
+    /// Returns the byte at position `idx`, or [`None`] if `idx` is out of
+    /// bounds.
+    ///
+    /// [`None`]: https://doc.rust-lang.org/nightly/std/option/enum.Option.html#variant.None
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("hi");
+    ///
+    /// assert_eq!(Some(104), s.byte_at(0));
+    /// assert_eq!(None, s.byte_at(10));
+    /// ```
     #[inline]
-    fn index(&self, index: std::ops::Range<usize>) -> &str {
-        &self[..][index]
+    pub fn byte_at(&self, idx: usize) -> Option<u8> {
+        self.as_bytes().get(idx).copied()
     }
-}
-
-impl std::ops::Index<std::ops::RangeTo<usize>> for String {
-    type Output = str;
 
+    /// Copies the bytes of the string into the start of `buf`, returning the
+    /// number of bytes copied.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Err`] with the number of bytes needed if `buf` is too small,
+    /// in which case nothing is copied.
+    ///
+    /// [`Err`]: https://doc.rust-lang.org/nightly/std/result/enum.Result.html#variant.Err
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("hi");
+    ///
+    /// let mut buf = [0; 10];
+    /// assert_eq!(Ok(2), s.as_bytes_into(&mut buf));
+    /// assert_eq!(b"hi", &buf[..2]);
+    ///
+    /// assert_eq!(Err(2), s.as_bytes_into(&mut [0; 1]));
+    /// ```
     #[inline]
-    fn index(&self, index: std::ops::RangeTo<usize>) -> &str {
-        &self[..][index]
+    pub fn as_bytes_into(&self, buf: &mut [u8]) -> Result<usize, usize> {
+        let bytes = self.as_bytes();
+        match buf.get_mut(..bytes.len()) {
+            Some(dest) => {
+                dest.copy_from_slice(bytes);
+                Ok(bytes.len())
+            },
+            None => Err(bytes.len())
+        }
     }
-}
-
-impl std::ops::Index<std::ops::RangeFrom<usize>> for String {
-    type Output = str;
 
+    /// The length of the string in UTF-16 code units
+    ///
+    /// This is the length of the buffer needed to hold the string after
+    /// [`encode_utf16`].
+    ///
+    /// [`encode_utf16`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.encode_utf16
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("a𐐷b");
+    ///
+    /// assert_eq!(4, s.utf16_len());
+    /// ```
     #[inline]
-    fn index(&self, index: std::ops::RangeFrom<usize>) -> &str {
-        &self[..][index]
+    pub fn utf16_len(&self) -> usize {
+        self.chars().map(char::len_utf16).sum()
     }
-}
-
-impl std::ops::Index<std::ops::RangeFull> for String {
-    type Output = str;
 
+    /// The number of [`char`]s within the byte range `range`
+    ///
+    /// This is useful for converting a byte offset into a column.
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if either end of `range` is out of bounds or does not lie on a
+    /// [`char`] boundary.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("a€b");
+    ///
+    /// assert_eq!(2, s.char_count(0..4));
+    /// assert_eq!(3, s.char_count(0..5));
+    /// ```
     #[inline]
-    fn index(&self, _index: std::ops::RangeFull) -> &str {
-        self
+    pub fn char_count(&self, range: std::ops::Range<usize>) -> usize {
+        self[range].chars().count()
     }
-}
-
-impl std::ops::Index<std::ops::RangeInclusive<usize>> for String {
-    type Output = str;
 
+    /// The zero-based column of the [`char`] that starts at byte `byte_idx`,
+    /// i.e. the number of [`char`]s before it.
+    ///
+    /// This is useful for turning parser offsets into columns for
+    /// diagnostics on a single line.
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if `byte_idx` is out of bounds or does not lie on a [`char`]
+    /// boundary.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("a€b");
+    ///
+    /// assert_eq!(2, s.char_column(4));
+    /// ```
     #[inline]
-    fn index(&self, index: std::ops::RangeInclusive<usize>) -> &str {
-        ::std::ops::Index::index(&**self, index)
+    pub fn char_column(&self, byte_idx: usize) -> usize {
+        self.char_count(0..byte_idx)
     }
-}
-
-impl std::ops::Index<std::ops::RangeToInclusive<usize>> for String {
-    type Output = str;
 
+    /// Counts the number of occurrences of the byte `b` in the string.
+    ///
+    /// This compares eight bytes at a time, which makes it faster than
+    /// filtering [`bytes`] for long strings. It is mostly useful for counting
+    /// ASCII delimiters before splitting.
+    ///
+    /// [`bytes`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.bytes
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("name,age,city,country");
+    ///
+    /// assert_eq!(3, s.count_byte(b','));
+    /// ```
     #[inline]
-    fn index(&self, index: std::ops::RangeToInclusive<usize>) -> &str {
-        ::std::ops::Index::index(&**self, index)
+    pub fn count_byte(&self, b: u8) -> usize {
+        let mut chunks = self.as_bytes().chunks_exact(8);
+        let mut count = 0;
+        for chunk in &mut chunks {
+            count += swar_matches(chunk, b).count_ones() as usize;
+        }
+        count + chunks.remainder().iter().filter(|&&x| x == b).count()
     }
-}
 
-impl std::ops::IndexMut<std::ops::Range<usize>> for String {
+    /// Returns true if the string contains the [`char`] `c`.
+    ///
+    /// For an ASCII `c` this scans eight bytes at a time, which is faster than
+    /// [`str::contains`] for long strings. Other characters fall back to
+    /// [`str::contains`].
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    /// [`str::contains`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.contains
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("Hello, World!");
+    ///
+    /// assert!(s.contains_char(','));
+    /// assert!(!s.contains_char('?'));
+    /// assert!(!s.contains_char('€'));
+    /// ```
     #[inline]
-    fn index_mut(&mut self, index: std::ops::Range<usize>) -> &mut str {
-        &mut self[..][index]
+    pub fn contains_char(&self, c: char) -> bool {
+        if c.is_ascii() {
+            self.find_byte(c as u8).is_some()
+        } else {
+            self.contains(c)
+        }
     }
-}
 
-impl std::ops::IndexMut<std::ops::RangeTo<usize>> for String {
-    #[inline]
-    fn index_mut(&mut self, index: std::ops::RangeTo<usize>) -> &mut str {
-        &mut self[..][index]
-    }
-}
-
-impl std::ops::IndexMut<std::ops::RangeFrom<usize>> for String {
-    #[inline]
-    fn index_mut(&mut self, index: std::ops::RangeFrom<usize>) -> &mut str {
-        &mut self[..][index]
-    }
-}
-
-impl std::ops::IndexMut<std::ops::RangeFull> for String {
-    #[inline]
-    fn index_mut(&mut self, _index: std::ops::RangeFull) -> &mut str {
-        self
-    }
-}
-
-impl std::ops::IndexMut<std::ops::RangeInclusive<usize>> for String {
+    /// Returns true if the string consists of exactly the [`char`] `c`.
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    ///
+    /// assert!(String::from("€").eq_char('€'));
+    /// assert!(!String::from("ab").eq_char('a'));
+    /// assert!(!String::new().eq_char('a'));
+    /// ```
     #[inline]
-    fn index_mut(&mut self, index: std::ops::RangeInclusive<usize>) -> &mut str {
-        std::ops::IndexMut::index_mut(&mut **self, index)
+    pub fn eq_char(&self, c: char) -> bool {
+        let mut bits = [0; 4];
+        self.as_bytes() == c.encode_utf8(&mut bits).as_bytes()
     }
-}
 
-impl std::ops::IndexMut<std::ops::RangeToInclusive<usize>> for String {
+    /// Returns a reference to the lesser of this string and `other`.
+    ///
+    /// Returns `self` if both are equal, like [`Ord::min`].
+    ///
+    /// [`Ord::min`]: https://doc.rust-lang.org/nightly/std/cmp/trait.Ord.html#method.min
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let words = [String::from("cherry"), String::from("apple"), String::from("banana")];
+    ///
+    /// let first = words[1..].iter().fold(&words[0], |a, b| a.min_ref(b));
+    /// assert_eq!("apple", first);
+    /// ```
     #[inline]
-    fn index_mut(&mut self, index: std::ops::RangeToInclusive<usize>) -> &mut str {
-        std::ops::IndexMut::index_mut(&mut **self, index)
+    pub fn min_ref<'a>(&'a self, other: &'a String) -> &'a String {
+        if other < self { other } else { self }
     }
-}
 
-impl From<std::string::String> for String {
+    /// Returns a reference to the greater of this string and `other`.
+    ///
+    /// Returns `other` if both are equal, like [`Ord::max`].
+    ///
+    /// [`Ord::max`]: https://doc.rust-lang.org/nightly/std/cmp/trait.Ord.html#method.max
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let words = [String::from("cherry"), String::from("apple"), String::from("banana")];
+    ///
+    /// let last = words[1..].iter().fold(&words[0], |a, b| a.max_ref(b));
+    /// assert_eq!("cherry", last);
+    /// ```
     #[inline]
-    fn from(item: std::string::String) -> String {
-        use std::mem;
-        let mut v = item.into_bytes();
-        let (capacity, data, len) = (v.capacity(), v.as_mut_ptr(), v.len());
-        mem::forget(v);
-        String {
-            len,
-            inner: Inner::Heap {
-                capacity,
-                data
-            }
-        }
+    pub fn max_ref<'a>(&'a self, other: &'a String) -> &'a String {
+        if other < self { self } else { other }
     }
-}
 
-impl<'a> From<&'a str> for String {
+    /// Returns the index of the first occurrence of the byte `b`, scanning
+    /// eight bytes at a time.
     #[inline]
-    fn from(item: &str) -> String {
-        String {
-            len: item.len(),
-            inner: match item.len() {
-                0...23 => {
-                    Inner::Stack {
-                        data: {
-                            let mut d = [0u8;23];
-                            d[..item.len()].copy_from_slice(item.as_bytes());
-                            d
-                        }
-                    }
-                },
-                len @ _ => {
-                    use std::ptr;
-                    let capacity = match len.checked_next_power_of_two() {
-                        Some(x) => x,
-                        None => len
-                    };
-                    Inner::Heap {
-                        capacity,
-                        data: {
-                            let d = alloc::alloc(capacity);
-                            unsafe {
-                                ptr::copy_nonoverlapping(item.as_ptr(), d, len);
-                            }
-                            d
-                        }
-                    }
-                }
+    fn find_byte(&self, b: u8) -> Option<usize> {
+        let bytes = self.as_bytes();
+        let mut chunks = bytes.chunks_exact(8);
+        let mut offset = 0;
+        for chunk in &mut chunks {
+            let matches = swar_matches(chunk, b);
+            if matches != 0 {
+                return Some(offset + (matches.trailing_zeros() / 8) as usize);
             }
+            offset += 8;
         }
+        chunks.remainder().iter().position(|&x| x == b).map(|idx| offset + idx)
     }
-}
-
-impl From<std::boxed::Box<str>> for String {
-    #[inline]
-    fn from(item: std::boxed::Box<str>) -> String {
-        item.into()
-    }
-}
 
-#[cfg(feature = "std")]
-impl std::net::ToSocketAddrs for String {
-    type Iter = std::option::IntoIter<std::net::SocketAddr>;
+    /// Returns true if the bytes of the string start with `prefix`.
+    ///
+    /// Unlike [`str::starts_with`], `prefix` does not need to be valid UTF-8
+    /// or end on a [`char`] boundary.
+    ///
+    /// [`str::starts_with`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.starts_with
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("héllo");
+    ///
+    /// // 'h' followed by the first byte of 'é'
+    /// assert!(s.starts_with_bytes(&[b'h', 0xc3]));
+    /// ```
     #[inline]
-    fn to_socket_addrs(&self) -> std::io::Result<Self::Iter> {
-        (&self).to_socket_addrs()
+    pub fn starts_with_bytes(&self, prefix: &[u8]) -> bool {
+        self.as_bytes().starts_with(prefix)
     }
-}
-
-#[derive(Clone, Copy)]
-pub enum ParseError {}
 
-impl std::str::FromStr for String {
-    type Err = ParseError;
+    /// Returns true if the bytes of the string end with `suffix`.
+    ///
+    /// Unlike [`str::ends_with`], `suffix` does not need to be valid UTF-8
+    /// or start on a [`char`] boundary.
+    ///
+    /// [`str::ends_with`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.ends_with
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("café");
+    ///
+    /// // The last byte of 'é'
+    /// assert!(s.ends_with_bytes(&[0xa9]));
+    /// ```
     #[inline]
-    fn from_str(s: &str) -> Result<String, ParseError> {
-        Ok(String::from(s))
+    pub fn ends_with_bytes(&self, suffix: &[u8]) -> bool {
+        self.as_bytes().ends_with(suffix)
     }
-}
 
-impl<'a> std::ops::Add<&'a str> for String {
-    type Output = String;
+    /// Returns true if the string starts with `prefix`, ignoring ASCII case.
+    ///
+    /// Only the ASCII letters `a` to `z` and `A` to `Z` are folded; no
+    /// lowercased copy of either string is allocated.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("Content-Type");
+    ///
+    /// assert!(s.starts_with_ignore_ascii_case("content-"));
+    /// assert!(!s.starts_with_ignore_ascii_case("type"));
+    /// ```
     #[inline]
-    fn add(mut self, other: &'a str) -> String {
-        self.push_str(other);
-        self
+    pub fn starts_with_ignore_ascii_case(&self, prefix: &str) -> bool {
+        let bytes = self.as_bytes();
+        bytes.len() >= prefix.len()
+            && bytes[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
     }
-}
 
-impl<'a> std::ops::AddAssign<&'a str> for String {
+    /// Returns true if the string ends with `suffix`, ignoring ASCII case.
+    ///
+    /// Only the ASCII letters `a` to `z` and `A` to `Z` are folded; no
+    /// lowercased copy of either string is allocated.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("IMAGE.PNG");
+    ///
+    /// assert!(s.ends_with_ignore_ascii_case(".png"));
+    /// assert!(!s.ends_with_ignore_ascii_case(".jpg"));
+    /// ```
     #[inline]
-    fn add_assign(&mut self, rhs: &'a str) {
-        self.push_str(rhs);
+    pub fn ends_with_ignore_ascii_case(&self, suffix: &str) -> bool {
+        let bytes = self.as_bytes();
+        bytes.len() >= suffix.len()
+            && bytes[bytes.len() - suffix.len()..].eq_ignore_ascii_case(suffix.as_bytes())
     }
-}
-
 
-impl Extend<char> for String {
-    fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
-        let iterator = iter.into_iter();
-        let (lower_bound, _) = iterator.size_hint();
-        self.reserve(lower_bound);
-        for ch in iterator {
-            self.push(ch)
+    /// Returns the uppercase equivalent of this string, as a new `String`.
+    ///
+    /// 'Uppercase' is defined according to the terms of the Unicode Derived
+    /// Core Property `Uppercase`. Characters may expand when uppercased, so
+    /// the result can be longer than the original.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("tschüß");
+    ///
+    /// assert_eq!("TSCHÜSS", s.to_uppercase());
+    /// ```
+    pub fn to_uppercase(&self) -> String {
+        let mut s = String::new();
+        s.reserve(self.len);
+        for c in self.chars() {
+            for u in c.to_uppercase() {
+                s.push(u);
+            }
         }
+        s
     }
-}
 
-impl<'a> Extend<&'a char> for String {
-    fn extend<I: IntoIterator<Item = &'a char>>(&mut self, iter: I) {
-        self.extend(iter.into_iter().cloned());
+    /// Returns the lowercase equivalent of this string, as a new `String`.
+    ///
+    /// 'Lowercase' is defined according to the terms of the Unicode Derived
+    /// Core Property `Lowercase`. Each character is mapped on its own, so
+    /// unlike [`str::to_lowercase`] a word-final 'Σ' becomes 'σ' rather than
+    /// 'ς'.
+    ///
+    /// [`str::to_lowercase`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.to_lowercase
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("HELLO");
+    ///
+    /// assert_eq!("hello", s.to_lowercase());
+    /// ```
+    pub fn to_lowercase(&self) -> String {
+        let mut s = String::new();
+        s.reserve(self.len);
+        for c in self.chars() {
+            for l in c.to_lowercase() {
+                s.push(l);
+            }
+        }
+        s
     }
-}
 
-impl<'a> Extend<&'a str> for String {
-    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, iter: I) {
-        for s in iter {
-            self.push_str(s)
-        }
+    /// Returns a copy of this string where each character is mapped to its
+    /// ASCII upper case equivalent.
+    ///
+    /// ASCII letters 'a' to 'z' are mapped to 'A' to 'Z', but non-ASCII
+    /// letters are unchanged. Unlike [`str::to_ascii_uppercase`], this returns
+    /// a `small::String`, so short results are stored on the stack.
+    ///
+    /// [`str::to_ascii_uppercase`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.to_ascii_uppercase
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("Grüße, Jürgen ❤");
+    ///
+    /// assert_eq!("GRüßE, JüRGEN ❤", s.to_ascii_uppercase());
+    /// ```
+    #[inline]
+    pub fn to_ascii_uppercase(&self) -> String {
+        let mut s = String::from(self.as_str());
+        s.make_ascii_uppercase();
+        s
     }
-}
 
-impl Extend<String> for String {
-    fn extend<I: IntoIterator<Item = String>>(&mut self, iter: I) {
-        for s in iter {
-            self.push_str(&s)
-        }
+    /// Returns a copy of this string where each character is mapped to its
+    /// ASCII lower case equivalent.
+    ///
+    /// ASCII letters 'A' to 'Z' are mapped to 'a' to 'z', but non-ASCII
+    /// letters are unchanged. Unlike [`str::to_ascii_lowercase`], this returns
+    /// a `small::String`, so short results are stored on the stack.
+    ///
+    /// [`str::to_ascii_lowercase`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.to_ascii_lowercase
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("Grüße, Jürgen ❤");
+    ///
+    /// assert_eq!("grüße, jürgen ❤", s.to_ascii_lowercase());
+    /// ```
+    #[inline]
+    pub fn to_ascii_lowercase(&self) -> String {
+        let mut s = String::from(self.as_str());
+        s.make_ascii_lowercase();
+        s
     }
-}
 
-impl std::iter::FromIterator<char> for String {
-    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> String {
-        let mut buf = String::new();
-        buf.extend(iter);
-        buf
-    }
-}
-
-impl<'a> std::iter::FromIterator<&'a char> for String {
-    fn from_iter<I: IntoIterator<Item = &'a char>>(iter: I) -> String {
-        let mut buf = String::new();
-        buf.extend(iter);
-        buf
-    }
-}
-
-impl<'a> std::iter::FromIterator<&'a str> for String {
-    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> String {
-        let mut buf = String::new();
-        buf.extend(iter);
-        buf
-    }
-}
-
-impl std::iter::FromIterator<String> for String {
-    fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> String {
-        let mut buf = String::new();
-        buf.extend(iter);
-        buf
-    }
-}
-
-impl PartialEq for String {
-    #[inline]
-    fn eq(&self, rhs: &Self) -> bool {
-        self.as_str() == rhs.as_str()
-    }
-}
-impl Eq for String { }
-
-impl PartialEq<String> for str {
-    #[inline]
-    fn eq(&self, rhs: &String) -> bool {
-        self == rhs.as_str()
-    }
-}
-impl PartialEq<str> for String {
-    #[inline]
-    fn eq(&self, rhs: &str) -> bool {
-        self.as_str() == rhs
-    }
-}
-impl<'a> PartialEq<&'a str> for String {
-    #[inline]
-    fn eq(&self, rhs: &&'a str) -> bool {
-        &self.as_str() == rhs
-    }
-}
-impl<'a> PartialEq<String> for &'a str {
-    #[inline]
-    fn eq(&self, rhs: &String) -> bool {
-        self == &rhs.as_str()
-    }
-}
-
-impl PartialOrd for String {
-    #[inline]
-    fn partial_cmp(&self, rhs: &Self) -> Option<::std::cmp::Ordering> {
-        self.as_str().partial_cmp(rhs.as_str())
-    }
-}
-impl Ord for String {
-    #[inline]
-    fn cmp(&self, rhs: &Self) -> ::std::cmp::Ordering {
-        self.as_str().cmp(rhs.as_str())
+    /// The mutable byte representation of the string
+    ///
+    /// The opposite of this function is [`from_utf8`]
+    ///
+    /// [`from_utf8`]: #method.from_utf8
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from_utf8(vec![64, 2]).unwrap();
+    ///
+    /// assert_eq!(&mut [64,2], unsafe { s.as_mut_bytes() });
+    /// ```
+    #[inline(always)]
+    pub unsafe fn as_mut_bytes(&mut self) -> &mut [u8] {
+        let len = self.len;
+        ::std::slice::from_raw_parts_mut(self.as_mut_ptr(), len)
     }
-}
 
-impl std::fmt::Write for String {
+    /// The mutable byte representation of the string, if it is on the heap
+    ///
+    /// Returns [`None`] for a string on the stack. Unlike [`as_mut_bytes`],
+    /// the returned bytes do not live inside the `String` itself, so a pointer
+    /// into them stays valid if the `String` is moved. Call [`ensure_heap`]
+    /// first to make sure this returns [`Some`].
+    ///
+    /// [`None`]: https://doc.rust-lang.org/nightly/std/option/enum.Option.html#variant.None
+    /// [`Some`]: https://doc.rust-lang.org/nightly/std/option/enum.Option.html#variant.Some
+    /// [`as_mut_bytes`]: #method.as_mut_bytes
+    /// [`ensure_heap`]: #method.ensure_heap
+    ///
+    /// # Safety
+    ///
+    /// The bytes must still be valid UTF-8 once the borrow ends.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from("hello");
+    /// assert!(unsafe { s.as_heap_bytes_mut() }.is_none());
+    ///
+    /// s.ensure_heap();
+    /// unsafe { s.as_heap_bytes_mut() }.unwrap().make_ascii_uppercase();
+    /// assert_eq!("HELLO", s);
+    /// ```
     #[inline]
-    fn write_str(&mut self, s: &str) -> std::fmt::Result {
-        Ok(self.push_str(s))
-    }
-
-    fn write_char(&mut self, c: char) -> std::fmt::Result {
-        Ok(self.push(c))
+    pub unsafe fn as_heap_bytes_mut(&mut self) -> Option<&mut [u8]> {
+        match self.inner {
+            Inner::Stack { .. } => None,
+            Inner::Heap { data, .. } => Some(::std::slice::from_raw_parts_mut(data, self.len))
+        }
     }
-}
 
-impl std::fmt::Display for String {
+    /// This string as a [`str`]
+    ///
+    /// [`str`]: https://doc.rust-lang.org/nightly/std/primitive.str.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("Hello!");
+    ///
+    /// assert_eq!("Hello!", s);
+    /// ```
     #[inline]
-    fn fmt(&self, fm: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        (self as &str).fmt(fm)
+    pub fn as_str(&self) -> &str {
+        self
     }
-}
 
-impl Drop for String {
+    /// Push a [`str`] onto the end of the string
+    ///
+    /// [`str`]: https://doc.rust-lang.org/nightly/std/primitive.str.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from("Hello");
+    /// s.push_str(" World!");
+    ///
+    /// assert_eq!("Hello World!", s);
+    /// ```
     #[inline]
-    fn drop(&mut self) {
-        if let Inner::Heap { capacity, data } = &self.inner {
-            if *capacity > 0 {
-                unsafe { alloc::dealloc(*data, *capacity) };
+    pub fn push_str(&mut self, item: &str) {
+        let new_len = match self.len.checked_add(item.len()) {
+            Some(x) => x,
+            None => panic!("capacity overflow")
+        };
+        // we match &mut self.inner so we don't copy the byte array
+        match (&mut self.inner, new_len) {
+            (Inner::Stack { data }, 0...23) => {
+                // Due to a compiler bug, [x..x+y] is more efficient than [x..][..y]
+                data[self.len..new_len].copy_from_slice(item.as_bytes());
+            },
+            (Inner::Heap { ref mut capacity, ref mut data, align_shift }, x) => {
+                if x > *capacity {
+                    let new_len = match new_len.checked_next_power_of_two() {
+                        Some(x) => x,
+                        None => new_len
+                    };
+                    Self::grow(capacity, data, *align_shift, new_len);
+                }
+                unsafe {
+                    ::std::ptr::copy_nonoverlapping(item.as_ptr(), data.add(self.len), item.len())
+                }
+            },
+            stack @ (Inner::Stack { .. } , _) => {
+                let capacity = match new_len.checked_next_power_of_two() {
+                    Some(x) => x,
+                    None => new_len
+                };
+                let d = if let Inner::Stack { ref data } = stack.0 {
+                    let d = alloc::alloc_or_abort(capacity);
+                    unsafe {
+                        ::std::ptr::copy_nonoverlapping(data.as_ptr(), d, self.len);
+                        ::std::ptr::copy_nonoverlapping(item.as_ptr(), d.add(self.len), item.len());
+                    }
+                    d
+                } else {
+                    //
+                    // We know from the match above that `stack.0` is definitely `Inner::Stack`.
+                    // Therefore we should never reach this location.
+                    //
+                    unsafe { unreachable_unchecked() }
+                };
+                *stack.0 = Inner::Heap {
+                    capacity: capacity,
+                    data: d,
+                    align_shift: 0
+                };
+                report_growth(23, capacity);
             }
         }
+        self.len = new_len;
     }
-}
 
-#[derive(Debug)]
-pub struct FromUtf8Error {
-    bytes: std::vec::Vec<u8>,
-    error: std::str::Utf8Error,
-}
-impl FromUtf8Error {
-    /// Returns a slice of [`u8`]s bytes that were attempted to convert to a `String`.
+    /// Push a [`str`] onto the end of the string, returning the number of
+    /// bytes appended
+    ///
+    /// This is useful for tracking progress while building a buffer
+    /// incrementally.
+    ///
+    /// [`str`]: https://doc.rust-lang.org/nightly/std/primitive.str.html
     ///
     /// # Examples
     ///
     /// Basic usage:
-    ///
     /// ```
     /// # extern crate small;
     /// use small::String;
-    /// // some invalid bytes, in a vector
-    /// let bytes = vec![0, 159];
-    ///
-    /// let value = String::from_utf8(bytes);
+    /// let mut s = String::from("Hello");
     ///
-    /// assert_eq!(&[0, 159], value.unwrap_err().as_bytes());
+    /// assert_eq!(7, s.push_str_counted(" World!"));
+    /// assert_eq!("Hello World!", s);
     /// ```
     #[inline]
-    pub fn as_bytes(&self) -> &[u8] {
-        &self.bytes[..]
+    pub fn push_str_counted(&mut self, item: &str) -> usize {
+        self.push_str(item);
+        item.len()
     }
 
-    /// Returns the bytes that were attempted to convert to a `String`.
+    /// Push a [`str`] onto the end of the string, then drop characters from
+    /// the front so that the string is at most `max_len` bytes long.
     ///
-    /// This method is carefully constructed to avoid allocation. It will
-    /// consume the error, moving out the bytes, so that a copy of the bytes
-    /// does not need to be made.
+    /// The string keeps the longest tail that fits in `max_len` bytes and
+    /// starts on a [`char`] boundary, so it may end up slightly shorter than
+    /// `max_len`. Bytes that would be dropped straight away are never copied
+    /// in, which keeps memory bounded when streaming.
+    ///
+    /// [`str`]: https://doc.rust-lang.org/nightly/std/primitive.str.html
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
     ///
     /// # Examples
     ///
     /// Basic usage:
-    ///
     /// ```
     /// # extern crate small;
     /// use small::String;
-    /// // some invalid bytes, in a vector
-    /// let bytes = vec![0, 159];
+    /// let mut s = String::from("hello ");
     ///
-    /// let value = String::from_utf8(bytes);
+    /// s.push_str_bounded("world", 8);
+    /// assert_eq!("lo world", s);
     ///
-    /// assert_eq!(vec![0, 159], value.unwrap_err().into_bytes());
+    /// s.push_str_bounded("€€€", 8);
+    /// assert_eq!("€€", s);
     /// ```
-    #[inline]
-    pub fn into_bytes(self) -> std::vec::Vec<u8> {
-        self.bytes
+    pub fn push_str_bounded(&mut self, item: &str, max_len: usize) {
+        let total = match self.len.checked_add(item.len()) {
+            Some(x) => x,
+            None => panic!("capacity overflow")
+        };
+        if total > max_len {
+            let mut cut = total - max_len;
+            if cut >= self.len {
+                // Nothing already in the string survives
+                let mut start = cut - self.len;
+                while !item.is_char_boundary(start) {
+                    start += 1;
+                }
+                self.len = 0;
+                self.push_str(&item[start..]);
+                return;
+            }
+            while !self.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.drain(..cut);
+        }
+        self.push_str(item);
     }
 
-    /// Fetch a `Utf8Error` to get more details about the conversion failure.
+    /// Push a [`str`] onto the end of the string, returning an error instead
+    /// of panicking or aborting if the string could not grow.
     ///
-    /// The [`Utf8Error`] type provided by [`std::str`] represents an error that may
-    /// occur when converting a slice of [`u8`]s to a [`&str`]. In this sense, it's
-    /// an analogue to `FromUtf8Error`. See its documentation for more details
-    /// on using it.
+    /// The string is left unchanged on failure.
     ///
-    /// [`Utf8Error`]: ../../std/str/struct.Utf8Error.html
-    /// [`std::str`]: ../../std/str/index.html
-    /// [`u8`]: ../../std/primitive.u8.html
-    /// [`&str`]: ../../std/primitive.str.html
+    /// [`str`]: https://doc.rust-lang.org/nightly/std/primitive.str.html
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as [`try_reserve`].
+    ///
+    /// [`try_reserve`]: #method.try_reserve
     ///
     /// # Examples
     ///
     /// Basic usage:
-    ///
     /// ```
     /// # extern crate small;
     /// use small::String;
-    /// // some invalid bytes, in a vector
-    /// let bytes = vec![0, 159];
-    ///
-    /// let error = String::from_utf8(bytes).unwrap_err().utf8_error();
+    /// let mut s = String::from("Hello");
     ///
-    /// // the first byte is invalid here
-    /// assert_eq!(1, error.valid_up_to());
+    /// assert_eq!(Ok(()), s.try_push_str(" World!"));
+    /// assert_eq!("Hello World!", s);
     /// ```
     #[inline]
-    pub fn utf8_error(&self) -> std::str::Utf8Error {
-        self.error
+    pub fn try_push_str(&mut self, item: &str) -> Result<(), TryReserveError> {
+        self.try_reserve(item.len())?;
+        self.push_str(item);
+        Ok(())
     }
-}
 
-#[cfg(all(feature = "serde", feature = "std"))]
-impl Serialize for String {
+    /// Push a [`char`] onto the end of the string, returning an error
+    /// instead of panicking or aborting if the string could not grow.
+    ///
+    /// The string is left unchanged on failure.
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as [`try_reserve`].
+    ///
+    /// [`try_reserve`]: #method.try_reserve
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from("Hello");
+    ///
+    /// assert_eq!(Ok(()), s.try_push('!'));
+    /// assert_eq!("Hello!", s);
+    /// ```
     #[inline]
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
+    pub fn try_push(&mut self, item: char) -> Result<(), TryReserveError> {
+        self.try_push_str(item.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Push every [`str`] yielded by `iter` onto the end of the string
+    ///
+    /// Before pushing, this reserves space for the first item plus a byte for
+    /// each further item the iterator reports. Any further growth is left to
+    /// [`push_str`], so a long first item does not inflate the reservation.
+    ///
+    /// [`push_str`]: #method.push_str
+    ///
+    /// [`str`]: https://doc.rust-lang.org/nightly/std/primitive.str.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from("Hello");
+    /// s.push_all(vec![" ", "World", "!"]);
+    ///
+    /// assert_eq!("Hello World!", s);
+    /// ```
+    #[inline]
+    pub fn push_all<'a, I>(&mut self, iter: I)
+        where I: IntoIterator<Item = &'a str>
     {
-        serializer.serialize_str(self)
+        let mut iter = iter.into_iter();
+        let first = match iter.next() {
+            Some(first) => first,
+            None => return,
+        };
+        let (lower_bound, _) = iter.size_hint();
+        if let Some(estimate) = first.len().checked_add(lower_bound) {
+            self.reserve(estimate);
+        }
+        self.push_str(first);
+        for s in iter {
+            self.push_str(s)
+        }
     }
-}
 
-#[cfg(all(feature = "serde", feature = "std"))]
-struct StringVisitor;
+    /// Push each run of `(ch, count)` yielded by `iter` onto the end of the
+    /// string, repeating `ch` `count` times
+    ///
+    /// Space for each run is reserved up front, which makes this more
+    /// efficient than expanding the runs into an iterator of [`char`]s.
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of a run in bytes overflows [`usize`].
+    ///
+    /// [`usize`]: https://doc.rust-lang.org/nightly/std/primitive.usize.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::new();
+    /// s.extend_runs(vec![('a', 3), ('b', 2)]);
+    ///
+    /// assert_eq!("aaabb", s);
+    /// ```
+    #[inline]
+    pub fn extend_runs<I>(&mut self, iter: I)
+        where I: IntoIterator<Item = (char, usize)>
+    {
+        for (ch, count) in iter {
+            let mut buf = [0; 4];
+            let encoded = ch.encode_utf8(&mut buf);
+            match encoded.len().checked_mul(count) {
+                Some(run_len) => self.reserve(run_len),
+                None => panic!("capacity overflow")
+            }
+            for _ in 0..count {
+                self.push_str(encoded)
+            }
+        }
+    }
 
-#[cfg(all(feature = "serde", feature = "std"))]
-struct StringInPlaceVisitor<'a>(&'a mut String);
+    /// Push a character onto the end of the string
+    ///
+    /// The opposite of this function is [`pop`]
+    ///
+    /// [`pop`]: #method.pop
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from("Hello");
+    /// s.push('!');
+    ///
+    /// assert_eq!("Hello!", s)
+    /// ```
+    #[inline]
+    pub fn push(&mut self, item: char) {
+        let ch_len = item.len_utf8();
+        let mut chs = [0; 4];
+        item.encode_utf8(&mut chs);
+        let new_len = match self.len.checked_add(ch_len) {
+            Some(x) => x,
+            None => panic!("capacity overflow")
+        };
+        // we match &mut self.inner so we don't copy the byte array
+        match (&mut self.inner, new_len) {
+            (Inner::Stack { data }, 0...23) => {
+                data[self.len..new_len].copy_from_slice(&chs[..ch_len]);
+            },
+            (Inner::Heap { ref mut capacity, ref mut data, align_shift }, x) => {
+                if x > *capacity {
+                    // Doubling alone is not enough when the buffer is empty
+                    let new_capacity = ::std::cmp::max(*capacity*2, x);
+                    Self::grow(capacity, data, *align_shift, new_capacity);
+                }
+                unsafe {
+                    ::std::ptr::copy_nonoverlapping(chs.as_ptr(), data.add(self.len), ch_len)
+                }
+            },
+            stack @ (Inner::Stack { .. }, _) => {
+                let d = if let Inner::Stack { ref data } = stack.0 {
+                    let d = alloc::alloc_or_abort(32);
+                    unsafe {
+                        ::std::ptr::copy_nonoverlapping(data.as_ptr(), d, self.len);
+                        ::std::ptr::copy_nonoverlapping(chs.as_ptr(), d.add(self.len), ch_len);
+                    }
+                    d
+                } else {
+                    //
+                    // We know from the match above that `stack.0` is definitely `Inner::Stack`.
+                    // Therefore we should never reach this location.
+                    //
+                    unsafe { unreachable_unchecked() }
+                };
+                *stack.0 = Inner::Heap {
+                    capacity: 32,
+                    data: d,
+                    align_shift: 0
+                };
+                report_growth(23, 32);
+            }
+        }
+        self.len = new_len;
+    }
 
-#[cfg(all(feature = "serde", feature = "std"))]
-impl<'de> Visitor<'de> for StringVisitor {
-    type Value = String;
+    /// Converts a vector of bytes to a `String`.
+    ///
+    /// A string slice ([`&str`]) is made of bytes ([`u8`]), and a vector of bytes
+    /// ([`Vec<u8>`]) is made of bytes, so this function converts between the
+    /// two. Not all byte slices are valid `String`s, however: `String`
+    /// requires that it is valid UTF-8. `from_utf8()` checks to ensure that
+    /// the bytes are valid UTF-8, and then does the conversion.
+    ///
+    /// If you are sure that the byte slice is valid UTF-8, and you don't want
+    /// to incur the overhead of the validity check, there is an unsafe version
+    /// of this function, [`from_utf8_unchecked`], which has the same behavior
+    /// but skips the check.
+    ///
+    /// This method will take care to not copy the vector, for efficiency's
+    /// sake.
+    ///
+    /// If you need a [`&str`] instead of a `String`, consider
+    /// [`str::from_utf8`].
+    ///
+    /// The inverse of this method is [`as_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Err`] if the slice is not UTF-8 with a description as to why the
+    /// provided bytes are not UTF-8. The vector you moved in is also included.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// // some bytes, in a vector
+    /// let sparkle_heart = vec![240, 159, 146, 150];
+    ///
+    /// // We know these bytes are valid, so we'll use `unwrap()`.
+    /// let sparkle_heart = String::from_utf8(sparkle_heart).unwrap();
+    ///
+    /// assert_eq!("💖", sparkle_heart);
+    /// ```
+    ///
+    /// Incorrect bytes:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// // some invalid bytes, in a vector
+    /// let sparkle_heart = vec![0, 159, 146, 150];
+    ///
+    /// assert!(String::from_utf8(sparkle_heart).is_err());
+    /// ```
+    ///
+    /// See the docs for [`FromUtf8Error`] for more details on what you can do
+    /// with this error.
+    ///
+    /// [`from_utf8_unchecked`]: struct.String.html#method.from_utf8_unchecked
+    /// [`&str`]: https://doc.rust-lang.org/nightly/std/primitive.str.html
+    /// [`u8`]: https://doc.rust-lang.org/nightly/std/primitive.u8.html
+    /// [`Vec<u8>`]: https://doc.rust-lang.org/nightly/std/vec/struct.Vec.html
+    /// [`str::from_utf8`]: https://doc.rust-lang.org/nightly/std/str/fn.from_utf8.html
+    /// [`as_bytes`]: struct.String.html#method.as_bytes
+    /// [`FromUtf8Error`]: struct.FromUtf8Error.html
+    /// [`Err`]: https://doc.rust-lang.org/nightly/std/result/enum.Result.html#variant.Err
+    #[inline]
+    pub fn from_utf8(vec: std::vec::Vec<u8>) -> Result<String, FromUtf8Error> {
+        use std::str;
+        match str::from_utf8(&vec) {
+            Ok(..) => Ok(unsafe { String::from_utf8_unchecked(vec) }),
+            Err(e) => {
+                Err(FromUtf8Error {
+                    bytes: vec,
+                    error: e
+                })
+            }
+        }
+    }
 
-    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.write_str("a string")
+    /// Checks whether `bytes` is valid UTF-8, returning the byte offset of
+    /// the first invalid sequence if it is not.
+    ///
+    /// This is a cheap pre-check before constructing a `String`. Unlike
+    /// [`from_utf8`] it does not take ownership of the bytes or build a
+    /// [`FromUtf8Error`].
+    ///
+    /// [`from_utf8`]: struct.String.html#method.from_utf8
+    /// [`FromUtf8Error`]: struct.FromUtf8Error.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    ///
+    /// assert_eq!(Ok(()), String::validate_utf8("💖".as_bytes()));
+    /// assert_eq!(Err(1), String::validate_utf8(&[104, 255]));
+    /// ```
+    #[inline]
+    pub fn validate_utf8(bytes: &[u8]) -> Result<(), usize> {
+        match ::std::str::from_utf8(bytes) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.valid_up_to())
+        }
+    }
+
+    /// Converts a slice of bytes to a string, including invalid characters.
+    ///
+    /// Each sequence of bytes that is not valid UTF-8 is replaced with
+    /// [`U+FFFD REPLACEMENT CHARACTER`][U+FFFD], which looks like this: �
+    ///
+    /// Unlike `std`'s version this always returns a `String`, so results of
+    /// 23 bytes or less are stored on the stack without allocating.
+    ///
+    /// [U+FFFD]: https://doc.rust-lang.org/nightly/std/char/constant.REPLACEMENT_CHARACTER.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// // some bytes, in a vector
+    /// let sparkle_heart = vec![240, 159, 146, 150];
+    ///
+    /// let sparkle_heart = String::from_utf8_lossy(&sparkle_heart);
+    ///
+    /// assert_eq!("💖", sparkle_heart);
+    /// ```
+    ///
+    /// Incorrect bytes:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// // some invalid bytes
+    /// let input = b"Hello \xF0\x90\x80World";
+    /// let output = String::from_utf8_lossy(input);
+    ///
+    /// assert_eq!("Hello �World", output);
+    /// ```
+    pub fn from_utf8_lossy(v: &[u8]) -> String {
+        let mut ret = String::new();
+        let mut input = v;
+        loop {
+            match ::std::str::from_utf8(input) {
+                Ok(valid) => {
+                    ret.push_str(valid);
+                    break;
+                },
+                Err(e) => {
+                    let (valid, after) = input.split_at(e.valid_up_to());
+                    ret.push_str(unsafe { ::std::str::from_utf8_unchecked(valid) });
+                    ret.push(::std::char::REPLACEMENT_CHARACTER);
+                    match e.error_len() {
+                        Some(n) => input = &after[n..],
+                        None => break
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    /// Converts a vector of bytes to a `String` without checking that the
+    /// string contains valid UTF-8.
+    ///
+    /// See the safe version, [`from_utf8`], for more details.
+    ///
+    /// [`from_utf8`]: struct.String.html#method.from_utf8
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because it does not check that the bytes passed
+    /// to it are valid UTF-8. If this constraint is violated, it may cause
+    /// memory unsafety issues with future users of the `String`, as the rest of
+    /// the standard library assumes that `String`s are valid UTF-8.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// // some bytes, in a vector
+    /// let sparkle_heart = vec![240, 159, 146, 150];
+    ///
+    /// let sparkle_heart = unsafe {
+    ///     String::from_utf8_unchecked(sparkle_heart)
+    /// };
+    ///
+    /// assert_eq!("💖", sparkle_heart);
+    /// ```
+    #[inline]
+    pub unsafe fn from_utf8_unchecked(mut vec: std::vec::Vec<u8>) -> String {
+        let (capacity, data, len) = (vec.capacity(), vec.as_mut_ptr(), vec.len());
+        let s = String {
+            len,
+            inner: Inner::Heap {
+                capacity,
+                data,
+                align_shift: 0
+            }
+        };
+        ::std::mem::forget(vec);
+        s
+    }
+
+    /// Converts a vector of bytes to a `String` without checking that the
+    /// string contains valid UTF-8, storing it on the stack if it fits.
+    ///
+    /// Vectors of 23 bytes or less are copied onto the stack and their buffer
+    /// is freed. Longer vectors are reused without copying, as in
+    /// [`from_utf8_unchecked`].
+    ///
+    /// [`from_utf8_unchecked`]: struct.String.html#method.from_utf8_unchecked
+    ///
+    /// # Safety
+    ///
+    /// The bytes passed in must be valid UTF-8, as in [`from_utf8_unchecked`].
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let hello = unsafe {
+    ///     String::from_utf8_unchecked_compact(b"hello".to_vec())
+    /// };
+    ///
+    /// assert_eq!("hello", hello);
+    /// assert!(!hello.overflowed());
+    /// ```
+    #[inline]
+    pub unsafe fn from_utf8_unchecked_compact(vec: std::vec::Vec<u8>) -> String {
+        if vec.len() <= 23 {
+            String::from(::std::str::from_utf8_unchecked(&vec))
+        } else {
+            String::from_utf8_unchecked(vec)
+        }
+    }
+
+    /// Decode a UTF-16 encoded slice `v` into a `String`, returning [`Err`]
+    /// if `v` contains any invalid data.
+    ///
+    /// Short results are stored on the stack, like any other `String`.
+    ///
+    /// [`Err`]: https://doc.rust-lang.org/nightly/std/result/enum.Result.html#variant.Err
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// // 𝄞music
+    /// let v = &[0xD834, 0xDD1E, 0x006d, 0x0075,
+    ///           0x0073, 0x0069, 0x0063];
+    /// assert_eq!(String::from("𝄞music"),
+    ///            String::from_utf16(v).unwrap());
+    ///
+    /// // 𝄞mu<invalid>ic
+    /// let v = &[0xD834, 0xDD1E, 0x006d, 0x0075,
+    ///           0xD800, 0x0069, 0x0063];
+    /// assert!(String::from_utf16(v).is_err());
+    /// ```
+    pub fn from_utf16(v: &[u16]) -> Result<String, FromUtf16Error> {
+        let mut ret = String::new();
+        ret.reserve(v.len());
+        for c in ::std::char::decode_utf16(v.iter().cloned()) {
+            match c {
+                Ok(c) => ret.push(c),
+                Err(_) => return Err(FromUtf16Error(()))
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Decode a UTF-16 encoded slice `v` into a `String`, replacing
+    /// invalid data with [the replacement character (`U+FFFD`)][U+FFFD].
+    ///
+    /// Short results are stored on the stack, like any other `String`.
+    ///
+    /// [U+FFFD]: https://doc.rust-lang.org/nightly/std/char/constant.REPLACEMENT_CHARACTER.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// // 𝄞mus<invalid>ic<invalid>
+    /// let v = &[0xD834, 0xDD1E, 0x006d, 0x0075,
+    ///           0x0073, 0xDD1E, 0x0069, 0x0063,
+    ///           0xD834];
+    ///
+    /// assert_eq!(String::from("𝄞mus\u{FFFD}ic\u{FFFD}"),
+    ///            String::from_utf16_lossy(v));
+    /// ```
+    pub fn from_utf16_lossy(v: &[u16]) -> String {
+        let mut ret = String::new();
+        ret.reserve(v.len());
+        for c in ::std::char::decode_utf16(v.iter().cloned()) {
+            ret.push(c.unwrap_or(::std::char::REPLACEMENT_CHARACTER));
+        }
+        ret
+    }
+
+    /// Returns the bytes that were attempted to convert to a `String`.
+    ///
+    /// This method is carefully constructed to avoid allocation. It will
+    /// consume the error, moving out the bytes, so that a copy of the bytes
+    /// does not need to be made.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// // some invalid bytes, in a vector
+    /// let bytes = vec![0, 159];
+    ///
+    /// let value = String::from_utf8(bytes);
+    ///
+    /// assert_eq!(vec![0, 159], value.unwrap_err().into_bytes());
+    /// ```
+    #[inline]
+    pub fn into_bytes(self) -> std::vec::Vec<u8> {
+        let v = match &self.inner {
+            Inner::Stack { ref data } => {
+                let mut v = ::std::vec::Vec::new();
+                v.extend_from_slice(&data[..self.len]);
+                v
+            },
+            Inner::Heap { ref capacity, ref data, align_shift: 0 } => {
+                unsafe {
+                    ::std::vec::Vec::from_raw_parts(*data, self.len, *capacity)
+                }
+            },
+            // A `Vec` would free the buffer with the wrong alignment
+            Inner::Heap { .. } => return self.as_bytes().to_vec()
+        };
+        ::std::mem::forget(self);
+        v
+    }
+
+    /// Converts this `String` into a [`Box`]`<`[`str`]`>`.
+    ///
+    /// A string on the heap is shrunk to fit and its buffer handed over to
+    /// the box, while a string on the stack is copied into an allocation of
+    /// exactly its length.
+    ///
+    /// [`Box`]: https://doc.rust-lang.org/nightly/std/boxed/struct.Box.html
+    /// [`str`]: https://doc.rust-lang.org/nightly/std/primitive.str.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("hello");
+    ///
+    /// let b = s.into_boxed_str();
+    /// assert_eq!("hello", &*b);
+    /// ```
+    pub fn into_boxed_str(self) -> std::boxed::Box<str> {
+        match self.inner {
+            Inner::Heap { capacity, data, align_shift: 0 } if self.len > 0 => {
+                let data = if capacity == self.len {
+                    // Already fitted, so skip the trip to the allocator
+                    data
+                } else {
+                    let d: *mut u8 = unsafe { alloc::realloc(data, capacity, self.len) };
+                    if d.is_null() {
+                        alloc::oom::<u8>(self.len)
+                    }
+                    d
+                };
+                let len = self.len;
+                ::std::mem::forget(self);
+                unsafe {
+                    std::boxed::Box::from_raw(
+                        ::std::ptr::slice_from_raw_parts_mut(data, len) as *mut str
+                    )
+                }
+            },
+            _ => std::boxed::Box::from(self.as_str())
+        }
+    }
+
+    /// Converts this `String` into a `std::string::String`.
+    ///
+    /// A string on the heap hands its buffer over without copying. A string
+    /// on the stack, or one from [`with_capacity_aligned`], is copied into a
+    /// new allocation.
+    ///
+    /// [`with_capacity_aligned`]: #method.with_capacity_aligned
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let s = String::from("abcdefghijklmnopqrstuvwxyz");
+    /// let ptr = s.as_ptr();
+    ///
+    /// let s: std::string::String = s.into_string();
+    /// assert_eq!("abcdefghijklmnopqrstuvwxyz", s);
+    /// assert_eq!(ptr, s.as_ptr());
+    /// ```
+    #[inline]
+    pub fn into_string(self) -> std::string::String {
+        match self.inner {
+            Inner::Stack { .. } => std::string::String::from(self.as_str()),
+            Inner::Heap { .. } => unsafe {
+                std::string::String::from_utf8_unchecked(self.into_bytes())
+            }
+        }
+    }
+
+    /// Converts a `String` into a mutable string slice.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from("foobar");
+    /// let s_mut_str = s.as_mut_str();
+    ///
+    /// s_mut_str.make_ascii_uppercase();
+    ///
+    /// assert_eq!("FOOBAR", s_mut_str);
+    /// ```
+    #[inline]
+    pub fn as_mut_str(&mut self) -> &mut str {
+        self
+    }
+
+    /// Divides the string into two mutable slices at the byte index `mid`.
+    ///
+    /// This forwards to [`str::split_at_mut`], and is provided so that it is
+    /// available without relying on `Deref`.
+    ///
+    /// [`str::split_at_mut`]: https://doc.rust-lang.org/nightly/std/primitive.str.html#method.split_at_mut
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid` is past the end of the string, or if it does not lie
+    /// on a [`char`] boundary.
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from("Hello World");
+    /// {
+    ///     let (first, last) = s.split_at_mut(5);
+    ///     first.make_ascii_uppercase();
+    ///     last.make_ascii_lowercase();
+    /// }
+    /// assert_eq!("HELLO world", s);
+    /// ```
+    #[inline]
+    pub fn split_at_mut(&mut self, mid: usize) -> (&mut str, &mut str) {
+        self.as_mut_str().split_at_mut(mid)
+    }
+
+    /// Returns a mutable sub-slice of this `String` over `range`.
+    ///
+    /// Unlike indexing, this returns [`None`] instead of panicking if the
+    /// range is out of bounds or does not lie on [`char`] boundaries.
+    ///
+    /// [`None`]: https://doc.rust-lang.org/nightly/std/option/enum.Option.html#variant.None
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from("abcd");
+    ///
+    /// s.substr_mut(1..3).unwrap().make_ascii_uppercase();
+    /// assert_eq!("aBCd", s);
+    ///
+    /// assert!(s.substr_mut(2..10).is_none());
+    /// ```
+    #[inline]
+    pub fn substr_mut<R>(&mut self, range: R) -> Option<&mut str>
+        where R: std::ops::RangeBounds<usize>
+    {
+        use std::ops::Bound;
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n.checked_add(1)?,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n.checked_add(1)?,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.len,
+        };
+        self.as_mut_str().get_mut(start..end)
+    }
+
+    /// Creates a draining iterator that removes the specified range in the
+    /// string and yields the removed chars.
+    ///
+    /// Note: The element range is removed even if the iterator is not
+    /// consumed until the end.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the starting point or end point do not lie on a [`char`]
+    /// boundary, or if they're out of bounds.
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from("α is alpha, β is beta");
+    /// let beta_offset = s.find('β').unwrap_or(s.len());
+    ///
+    /// // Remove the range up until the β from the string
+    /// let t: String = s.drain(..beta_offset).collect();
+    /// assert_eq!(t, "α is alpha, ");
+    /// assert_eq!(s, "β is beta");
+    ///
+    /// // A full range clears the string
+    /// s.drain(..);
+    /// assert_eq!(s, "");
+    /// ```
+    pub fn drain<R>(&mut self, range: R) -> Drain<'_>
+        where R: std::ops::RangeBounds<usize>
+    {
+        let (start, end) = self.char_range(range);
+
+        // The iterator borrows the string through a raw pointer so that the
+        // drop of `Drain` can close the gap in the buffer
+        let string = self as *mut String;
+        let iter = unsafe { self.get_unchecked(start..end) }.chars();
+        Drain {
+            string,
+            start,
+            end,
+            iter
+        }
+    }
+
+    /// Removes the specified range in the string, and replaces it with the
+    /// given string. The given string doesn't need to be the same length as
+    /// the range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the starting point or end point do not lie on a [`char`]
+    /// boundary, or if they're out of bounds.
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from("α is alpha, β is beta");
+    /// let beta_offset = s.find('β').unwrap_or(s.len());
+    ///
+    /// // Replace the range up until the β from the string
+    /// s.replace_range(..beta_offset, "Α is capital alpha; ");
+    /// assert_eq!(s, "Α is capital alpha; β is beta");
+    /// ```
+    pub fn replace_range<R>(&mut self, range: R, replace_with: &str)
+        where R: std::ops::RangeBounds<usize>
+    {
+        use std::ptr;
+        let (start, end) = self.char_range(range);
+        let amt = replace_with.len();
+        let tail = self.len - end;
+        if amt > end - start {
+            self.reserve(amt - (end - start));
+        }
+        unsafe {
+            ptr::copy(self.as_ptr().add(end),
+                      self.as_mut_ptr().add(start + amt),
+                      tail);
+            ptr::copy_nonoverlapping(replace_with.as_ptr(),
+                                     self.as_mut_ptr().add(start),
+                                     amt);
+        }
+        self.len = start + amt + tail;
+    }
+
+    /// Copies elements from `src` range to the end of the string.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the starting point or end point do not lie on a [`char`]
+    /// boundary, or if they're out of bounds.
+    ///
+    /// [`char`]: https://doc.rust-lang.org/nightly/std/primitive.char.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut string = String::from("abcde");
+    ///
+    /// string.extend_from_within(2..);
+    /// assert_eq!(string, "abcdecde");
+    ///
+    /// string.extend_from_within(..2);
+    /// assert_eq!(string, "abcdecdeab");
+    ///
+    /// string.extend_from_within(4..8);
+    /// assert_eq!(string, "abcdecdeabecde");
+    /// ```
+    pub fn extend_from_within<R>(&mut self, src: R)
+        where R: std::ops::RangeBounds<usize>
+    {
+        let (start, end) = self.char_range(src);
+        let amt = end - start;
+        self.reserve(amt);
+        // The buffer may have moved while reserving, so only take pointers
+        // into it afterwards
+        unsafe {
+            let data = self.as_mut_ptr();
+            ::std::ptr::copy_nonoverlapping(data.add(start), data.add(self.len), amt);
+        }
+        self.len += amt;
+    }
+
+    /// Resolves `range` to a pair of byte offsets, panicking if either end is
+    /// out of bounds or does not lie on a char boundary.
+    fn char_range<R>(&self, range: R) -> (usize, usize)
+        where R: std::ops::RangeBounds<usize>
+    {
+        use std::ops::Bound;
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n.checked_add(1).expect("range start overflows"),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n.checked_add(1).expect("range end overflows"),
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.len,
+        };
+        assert!(start <= end);
+        assert!(self.is_char_boundary(start));
+        assert!(self.is_char_boundary(end));
+        (start, end)
+    }
+
+    /// Shrinks the capacity of the string to be the same as the length of their
+    /// string. While allocated on the stack, this is a no-op
+    ///
+    /// A string on the heap whose contents fit in 23 bytes is moved back onto
+    /// the stack, and its heap buffer is freed.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    ///
+    /// // On the stack
+    /// let mut s = String::from("Hello!");
+    /// assert_eq!(23, s.capacity());
+    /// s.shrink_to_fit();
+    /// assert_eq!(23, s.capacity());
+    ///
+    /// // On the heap
+    /// let mut s = String::from("abcdefghijklmnopqrstuvwxyz");
+    /// assert_eq!(32, s.capacity());
+    /// s.shrink_to_fit();
+    /// assert_eq!(26, s.capacity());
+    ///
+    /// // Back onto the stack
+    /// s.truncate(5);
+    /// s.shrink_to_fit();
+    /// assert_eq!(23, s.capacity());
+    /// assert!(!s.overflowed());
+    /// ```
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        if self.len <= 23 {
+            self.move_to_stack();
+            return;
+        }
+        let len = self.len;
+        self.shrink_heap(len);
+    }
+
+    /// Shrinks the capacity of the string with a lower bound.
+    ///
+    /// The capacity will remain at least as large as both the length and the
+    /// supplied value. If the current capacity is less than the lower limit,
+    /// this is a no-op. While allocated on the stack, this is a no-op.
+    ///
+    /// A string on the heap that would shrink to 23 bytes or less is moved
+    /// back onto the stack, and its heap buffer is freed.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from("abcdefghijklmnopqrstuvwxyz");
+    /// s.reserve(100);
+    /// assert_eq!(128, s.capacity());
+    ///
+    /// s.shrink_to(64);
+    /// assert_eq!(64, s.capacity());
+    ///
+    /// s.shrink_to(0);
+    /// assert_eq!(26, s.capacity());
+    /// ```
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        if let Inner::Heap { capacity, .. } = self.inner {
+            let new_cap = ::std::cmp::max(self.len, min_capacity);
+            if new_cap >= capacity {
+                return;
+            }
+            if new_cap <= 23 {
+                self.move_to_stack();
+            } else {
+                self.shrink_heap(new_cap);
+            }
+        }
+    }
+
+    /// Sets the capacity of the string to exactly `capacity` bytes, growing
+    /// or shrinking its buffer as needed.
+    ///
+    /// A string on the stack is moved onto the heap if `capacity` is more
+    /// than 23 bytes, and is otherwise left alone. A capacity of `0` moves a
+    /// heap string back onto the stack.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is less than the length of the string.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from("0123456789");
+    ///
+    /// s.set_capacity(100);
+    /// assert_eq!(100, s.capacity());
+    ///
+    /// s.set_capacity(50);
+    /// assert_eq!(50, s.capacity());
+    /// assert_eq!("0123456789", s);
+    /// ```
+    pub fn set_capacity(&mut self, capacity: usize) {
+        assert!(capacity >= self.len, "capacity is less than the length of the string");
+        match self.inner {
+            Inner::Stack { .. } => {
+                if capacity > 23 {
+                    self.reserve_exact(capacity - self.len);
+                }
+            },
+            Inner::Heap { capacity: old, .. } => {
+                if capacity == 0 {
+                    self.move_to_stack();
+                } else if capacity > old {
+                    self.reserve_exact(capacity - self.len);
+                } else {
+                    self.shrink_heap(capacity);
+                }
+            }
+        }
+    }
+
+    /// Reallocates a heap buffer down to `new_cap` bytes, which must be at
+    /// least the length of the string and greater than zero.
+    #[inline]
+    fn shrink_heap(&mut self, new_cap: usize) {
+        if let Inner::Heap { ref mut capacity, ref mut data, align_shift } = &mut self.inner {
+            if *capacity == new_cap {
+                // Already fitted, so skip the trip to the allocator
+                return;
+            }
+            let layout = match Self::heap_layout(*capacity, *align_shift) {
+                Ok(layout) => layout,
+                Err(_) => return
+            };
+            // A smaller size with the same alignment always forms a valid layout
+            let d: *mut u8 = unsafe { alloc::realloc_aligned(*data, layout, new_cap) };
+            if d.is_null() {
+                alloc::oom::<u8>(new_cap)
+            }
+            *data = d;
+            *capacity = new_cap;
+        }
+    }
+
+    /// Ensures that this `String`'s capacity is at least `additional` bytes
+    /// larger than its length.
+    ///
+    /// The capacity may be increased by more than `additional` bytes if it
+    /// chooses, to prevent frequent reallocations.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new capacity overflows [`usize`].
+    ///
+    /// [`usize`]: ../../std/primitive.usize.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// let mut s = String::new();
+    ///
+    /// s.reserve(10);
+    ///
+    /// assert!(s.capacity() >= 10);
+    /// ```
+    ///
+    /// This may not actually increase the capacity:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    ///
+    /// // Lets create a string on the heap
+    /// let mut s = String::with_capacity(10);
+    /// s.push('a');
+    /// s.push('b');
+    ///
+    /// // s now has a length of 2 and a capacity of 10
+    /// assert_eq!(2, s.len());
+    /// assert_eq!(10, s.capacity());
+    ///
+    /// // Since we already have an extra 8 capacity, calling this...
+    /// s.reserve(8);
+    ///
+    /// // ... doesn't actually increase.
+    /// assert_eq!(10, s.capacity());
+    /// ```
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        Self::handle_reserve(self.try_reserve(additional));
+    }
+
+    /// Ensures that this `String`'s capacity is exactly `additional` bytes
+    /// larger than its length, if it needs to grow at all.
+    ///
+    /// Unlike [`reserve`], this does not round the new capacity up to the
+    /// next power of two, which avoids over-allocating large strings. A
+    /// string whose new capacity fits in 23 bytes stays on the stack.
+    ///
+    /// [`reserve`]: #method.reserve
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new capacity overflows [`usize`].
+    ///
+    /// [`usize`]: ../../std/primitive.usize.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::from("abcdefghijklmnopqrstuvwxyz");
+    /// s.shrink_to_fit();
+    ///
+    /// s.reserve_exact(10);
+    ///
+    /// assert_eq!(36, s.capacity());
+    /// ```
+    #[inline]
+    pub fn reserve_exact(&mut self, additional: usize) {
+        Self::handle_reserve(self.try_reserve_exact(additional));
+    }
+
+    /// Panics or aborts if `result` is an error from one of the `try_reserve`
+    /// family.
+    #[inline]
+    fn handle_reserve(result: Result<(), TryReserveError>) {
+        match result {
+            Ok(()) => {},
+            Err(TryReserveError::CapacityOverflow) => panic!("capacity overflow"),
+            Err(TryReserveError::AllocError { capacity }) => alloc::oom::<u8>(capacity)
+        }
+    }
+
+    /// Tries to ensure that this `String`'s capacity is at least `additional`
+    /// bytes larger than its length.
+    ///
+    /// This behaves like [`reserve`], except that errors are returned instead
+    /// of panicking or aborting. On error the string is left unchanged.
+    ///
+    /// [`reserve`]: #method.reserve
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TryReserveError::CapacityOverflow`] if the new capacity
+    /// overflows, or [`TryReserveError::AllocError`] if the allocator fails.
+    ///
+    /// [`TryReserveError::CapacityOverflow`]: enum.TryReserveError.html#variant.CapacityOverflow
+    /// [`TryReserveError::AllocError`]: enum.TryReserveError.html#variant.AllocError
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// use small::string::TryReserveError;
+    ///
+    /// let mut s = String::from("Hello!");
+    ///
+    /// assert_eq!(Ok(()), s.try_reserve(30));
+    /// assert!(s.capacity() >= 36);
+    ///
+    /// assert_eq!(Err(TryReserveError::CapacityOverflow), s.try_reserve(usize::MAX));
+    /// assert_eq!("Hello!", s);
+    /// ```
+    #[inline]
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.try_reserve_inner(additional, false)
+    }
+
+    /// Tries to ensure that this `String`'s capacity is exactly `additional`
+    /// bytes larger than its length, if it needs to grow at all.
+    ///
+    /// This behaves like [`reserve_exact`], except that errors are returned
+    /// instead of panicking or aborting. On error the string is left
+    /// unchanged.
+    ///
+    /// [`reserve_exact`]: #method.reserve_exact
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as [`try_reserve`].
+    ///
+    /// [`try_reserve`]: #method.try_reserve
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    ///
+    /// let mut s = String::from("Hello!");
+    ///
+    /// assert_eq!(Ok(()), s.try_reserve_exact(30));
+    /// assert_eq!(36, s.capacity());
+    /// ```
+    #[inline]
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.try_reserve_inner(additional, true)
+    }
+
+    /// Grows the string to hold `additional` more bytes, rounding the new
+    /// capacity up to a power of two unless `exact` is set.
+    fn try_reserve_inner(&mut self, additional: usize, exact: bool) -> Result<(), TryReserveError> {
+        if additional == 0 {
+            return Ok(());
+        }
+        if let Inner::Heap { capacity, .. } = self.inner {
+            if additional <= capacity - self.len {
+                return Ok(());
+            }
+        }
+        let new_cap = match self.len.checked_add(additional) {
+            // Allocations larger than isize::MAX bytes are not allowed
+            Some(x) if x <= isize::MAX as usize => x,
+            _ => return Err(TryReserveError::CapacityOverflow)
+        };
+        let new_len = match new_cap.checked_next_power_of_two() {
+            Some(x) if !exact && x <= isize::MAX as usize => x,
+            _ => new_cap
+        };
+        // we match &mut self.inner so we don't copy the byte array
+        match (&mut self.inner, new_cap) {
+            (Inner::Stack { data: _ }, 0...23) => Ok(()),
+            (Inner::Heap { ref mut capacity, ref mut data, align_shift }, _) => {
+                Self::try_grow(capacity, data, *align_shift, new_len)
+            },
+            stack @ (Inner::Stack { .. }, _) => {
+                let d = if let Inner::Stack { ref data } = stack.0 {
+                    let d: *mut u8 = alloc::alloc(new_len);
+                    if d.is_null() {
+                        return Err(TryReserveError::AllocError { capacity: new_len });
+                    }
+                    unsafe {
+                        ::std::ptr::copy_nonoverlapping(data.as_ptr(), d, self.len);
+                    }
+                    d
+                } else {
+                    //
+                    // We know from the match above that `stack.0` is definitely `Inner::Stack`.
+                    // Therefore we should never reach this location.
+                    //
+                    unsafe { unreachable_unchecked() }
+                };
+                *stack.0 = Inner::Heap {
+                    capacity: new_len,
+                    data: d,
+                    align_shift: 0
+                };
+                report_growth(23, new_len);
+                Ok(())
+            }
+        }
+    }
+
+    /// Reserves space for `additional` more bytes, reporting whether the
+    /// string is on the heap afterwards.
+    ///
+    /// A string on the stack that can hold `additional` more bytes inline is
+    /// left on the stack and `false` is returned, letting callers who can
+    /// tolerate incremental growth defer the allocation. Otherwise this
+    /// behaves like [`reserve`] and returns `true`.
+    ///
+    /// [`reserve`]: #method.reserve
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::new();
+    ///
+    /// assert!(!s.reserve_or_inline(10));
+    /// assert!(!s.overflowed());
+    ///
+    /// assert!(s.reserve_or_inline(30));
+    /// assert!(s.capacity() >= 30);
+    /// ```
+    #[inline]
+    pub fn reserve_or_inline(&mut self, additional: usize) -> bool {
+        let fits_inline = match self.len.checked_add(additional) {
+            Some(new_len) => new_len <= 23,
+            None => false
+        };
+        if !self.overflowed() && fits_inline {
+            return false;
+        }
+        self.reserve(additional);
+        true
+    }
+
+    /// Reserves space for `additional` more bytes, as in [`reserve`], and
+    /// returns the resulting capacity.
+    ///
+    /// [`reserve`]: #method.reserve
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// let mut s = String::new();
+    ///
+    /// assert_eq!(23, s.reserve_returning(10));
+    /// assert_eq!(32, s.reserve_returning(30));
+    /// ```
+    #[inline]
+    pub fn reserve_returning(&mut self, additional: usize) -> usize {
+        self.reserve(additional);
+        self.capacity()
+    }
+
+    #[inline]
+    fn grow(capacity: &mut usize, data: &mut *mut u8, align_shift: u8, new_cap: usize) {
+        Self::handle_reserve(Self::try_grow(capacity, data, align_shift, new_cap));
+    }
+
+    /// Grows a heap buffer to `new_cap` bytes, leaving it untouched if the
+    /// allocation fails.
+    #[inline]
+    fn try_grow(capacity: &mut usize, data: &mut *mut u8, align_shift: u8, new_cap: usize) -> Result<(), TryReserveError> {
+        // An empty buffer (e.g. from an empty `Vec`) is dangling rather than
+        // allocated, so it must be allocated afresh instead of reallocated
+        let new_layout = Self::heap_layout(new_cap, align_shift)?;
+        let d: *mut u8 = if *capacity == 0 {
+            alloc::alloc_aligned(new_layout)
+        } else {
+            let layout = Self::heap_layout(*capacity, align_shift)?;
+            unsafe { alloc::realloc_aligned(*data, layout, new_cap) }
+        };
+        if d.is_null() {
+            return Err(TryReserveError::AllocError { capacity: new_cap });
+        }
+        report_growth(*capacity, new_cap);
+        *data = d;
+        *capacity = new_cap;
+        Ok(())
+    }
+
+    /// The layout of a heap buffer of `capacity` bytes aligned to
+    /// `1 << align_shift` bytes.
+    #[inline]
+    fn heap_layout(capacity: usize, align_shift: u8) -> Result<Layout, TryReserveError> {
+        Layout::from_size_align(capacity, 1 << align_shift)
+            .map_err(|_| TryReserveError::CapacityOverflow)
+    }
+
+    /// Frees a heap buffer of `capacity` bytes aligned to `1 << align_shift`
+    /// bytes.
+    #[inline]
+    unsafe fn dealloc_heap(data: *mut u8, capacity: usize, align_shift: u8) {
+        // The buffer was allocated with this layout, so it is always valid
+        if let Ok(layout) = Self::heap_layout(capacity, align_shift) {
+            alloc::dealloc_aligned(data, layout);
+        }
+    }
+
+    /// Clears the string. This performs no deallocation, so any string on the
+    /// heap will remain allocated on the heap.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    ///
+    /// let mut s = String::from("Hello!");
+    /// assert_eq!(6, s.len());
+    /// assert_eq!("Hello!", s);
+    ///
+    /// s.clear();
+    /// assert_eq!(0, s.len());
+    /// assert_eq!("", s);
+    /// ```
+    #[inline]
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Clears the string and frees any heap allocation, moving it back onto
+    /// the stack. This is a no-op for the allocation of a string on the stack.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    ///
+    /// let mut s = String::from("abcdefghijklmnopqrstuvwxyz");
+    /// assert!(s.overflowed());
+    ///
+    /// s.reset();
+    /// assert_eq!("", s);
+    /// assert_eq!(23, s.capacity());
+    /// assert!(!s.overflowed());
+    /// ```
+    #[inline]
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.move_to_stack();
+    }
+
+    /// Overwrites the contents of this string with `s`, reusing the existing
+    /// buffer where possible.
+    ///
+    /// This is the equivalent of [`ToOwned::clone_into`] for a `String`
+    /// backed buffer: a string on the heap keeps its allocation as long as it
+    /// is large enough to hold `s`.
+    ///
+    /// [`ToOwned::clone_into`]: https://doc.rust-lang.org/nightly/std/borrow/trait.ToOwned.html#method.clone_into
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    ///
+    /// let mut s = String::from("abcdefghijklmnopqrstuvwxyz");
+    /// let ptr = s.as_ptr();
+    ///
+    /// s.clone_from_str("Hello!");
+    /// assert_eq!("Hello!", s);
+    /// assert_eq!(ptr, s.as_ptr());
+    /// ```
+    #[inline]
+    pub fn clone_from_str(&mut self, s: &str) {
+        self.clear();
+        self.push_str(s);
+    }
+
+    /// Exchanges the contents of this string with `other`, without copying
+    /// any heap data.
+    ///
+    /// The storage of each string moves along with its contents, so a string
+    /// on the stack swapped with one on the heap ends up on the heap.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    ///
+    /// let mut a = String::from("stack");
+    /// let mut b = String::from("abcdefghijklmnopqrstuvwxyz");
+    ///
+    /// a.swap(&mut b);
+    ///
+    /// assert_eq!("abcdefghijklmnopqrstuvwxyz", a);
+    /// assert!(a.overflowed());
+    /// assert_eq!("stack", b);
+    /// assert!(!b.overflowed());
+    /// ```
+    #[inline]
+    pub fn swap(&mut self, other: &mut String) {
+        ::std::mem::swap(self, other);
+    }
+}
+
+impl AsRef<str> for String {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self
+    }
+}
+
+impl AsRef<[u8]> for String {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl Default for String {
+    #[inline]
+    fn default() -> String {
+        String::new()
+    }
+}
+
+impl Borrow<str> for String {
+    #[inline]
+    fn borrow(&self) -> &str {
+        self
+    }
+}
+
+impl ::std::ops::Deref for String {
+    type Target = str;
+    #[inline(always)]
+    fn deref(&self) -> &str {
+        unsafe {
+            ::std::str::from_utf8_unchecked(self.as_bytes())
+        }
+    }
+}
+
+impl ::std::ops::DerefMut for String {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut str {
+        unsafe {
+            ::std::str::from_utf8_unchecked_mut(self.as_mut_bytes())
+        }
+    }
+}
+
+impl Clone for String {
+    #[inline]
+    fn clone(&self) -> Self {
+        String {
+            len: self.len,
+            inner: match (self.inner, self.len) {
+                stack @ (Inner::Stack { .. }, _) => stack.0,
+                (Inner::Heap { data, .. }, 0...23) => {
+                    Inner::Stack {
+                        data: {
+                            let mut d = [0u8;23];
+                            d[..self.len].copy_from_slice(
+                                unsafe {
+                                    ::std::slice::from_raw_parts(data, self.len)
+                                });
+                            d
+                        }
+                    }
+                },
+                (Inner::Heap { capacity, data, align_shift }, _) => {
+                    use std::ptr;
+                    Inner::Heap {
+                        capacity,
+                        data: {
+                            let d = match String::heap_layout(capacity, align_shift) {
+                                Ok(layout) => alloc::alloc_aligned(layout),
+                                Err(_) => panic!("capacity overflow")
+                            };
+                            if d.is_null() {
+                                alloc::oom::<u8>(capacity)
+                            }
+                            unsafe {
+                                ptr::copy_nonoverlapping(data, d, self.len);
+                            }
+                            d
+                        },
+                        align_shift
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl std::hash::Hash for String {
+    #[inline]
+    fn hash<H: std::hash::Hasher>(&self, hs: &mut H) {
+        (**self).hash(hs)
+    }
+}
+
+impl std::ops::Index<std::ops::Range<usize>> for String {
+    type Output = str;
+
+    #[inline]
+    fn index(&self, index: std::ops::Range<usize>) -> &str {
+        &self[..][index]
+    }
+}
+
+impl std::ops::Index<std::ops::RangeTo<usize>> for String {
+    type Output = str;
+
+    #[inline]
+    fn index(&self, index: std::ops::RangeTo<usize>) -> &str {
+        &self[..][index]
+    }
+}
+
+impl std::ops::Index<std::ops::RangeFrom<usize>> for String {
+    type Output = str;
+
+    #[inline]
+    fn index(&self, index: std::ops::RangeFrom<usize>) -> &str {
+        &self[..][index]
+    }
+}
+
+impl std::ops::Index<std::ops::RangeFull> for String {
+    type Output = str;
+
+    #[inline]
+    fn index(&self, _index: std::ops::RangeFull) -> &str {
+        self
+    }
+}
+
+impl std::ops::Index<std::ops::RangeInclusive<usize>> for String {
+    type Output = str;
+
+    #[inline]
+    fn index(&self, index: std::ops::RangeInclusive<usize>) -> &str {
+        ::std::ops::Index::index(&**self, index)
+    }
+}
+
+impl std::ops::Index<std::ops::RangeToInclusive<usize>> for String {
+    type Output = str;
+
+    #[inline]
+    fn index(&self, index: std::ops::RangeToInclusive<usize>) -> &str {
+        ::std::ops::Index::index(&**self, index)
+    }
+}
+
+impl std::ops::IndexMut<std::ops::Range<usize>> for String {
+    #[inline]
+    fn index_mut(&mut self, index: std::ops::Range<usize>) -> &mut str {
+        &mut self[..][index]
+    }
+}
+
+impl std::ops::IndexMut<std::ops::RangeTo<usize>> for String {
+    #[inline]
+    fn index_mut(&mut self, index: std::ops::RangeTo<usize>) -> &mut str {
+        &mut self[..][index]
+    }
+}
+
+impl std::ops::IndexMut<std::ops::RangeFrom<usize>> for String {
+    #[inline]
+    fn index_mut(&mut self, index: std::ops::RangeFrom<usize>) -> &mut str {
+        &mut self[..][index]
+    }
+}
+
+impl std::ops::IndexMut<std::ops::RangeFull> for String {
+    #[inline]
+    fn index_mut(&mut self, _index: std::ops::RangeFull) -> &mut str {
+        self
+    }
+}
+
+impl std::ops::IndexMut<std::ops::RangeInclusive<usize>> for String {
+    #[inline]
+    fn index_mut(&mut self, index: std::ops::RangeInclusive<usize>) -> &mut str {
+        std::ops::IndexMut::index_mut(&mut **self, index)
+    }
+}
+
+impl std::ops::IndexMut<std::ops::RangeToInclusive<usize>> for String {
+    #[inline]
+    fn index_mut(&mut self, index: std::ops::RangeToInclusive<usize>) -> &mut str {
+        std::ops::IndexMut::index_mut(&mut **self, index)
+    }
+}
+
+impl From<std::string::String> for String {
+    #[inline]
+    fn from(item: std::string::String) -> String {
+        use std::mem;
+        let mut v = item.into_bytes();
+        let (capacity, data, len) = (v.capacity(), v.as_mut_ptr(), v.len());
+        mem::forget(v);
+        String {
+            len,
+            inner: Inner::Heap {
+                capacity,
+                data,
+                align_shift: 0
+            }
+        }
+    }
+}
+
+impl<'a> From<&'a str> for String {
+    #[inline]
+    fn from(item: &str) -> String {
+        String {
+            len: item.len(),
+            inner: match item.len() {
+                0...23 => {
+                    Inner::Stack {
+                        data: {
+                            let mut d = [0u8;23];
+                            d[..item.len()].copy_from_slice(item.as_bytes());
+                            d
+                        }
+                    }
+                },
+                len @ _ => {
+                    use std::ptr;
+                    let capacity = match len.checked_next_power_of_two() {
+                        Some(x) => x,
+                        None => len
+                    };
+                    Inner::Heap {
+                        capacity,
+                        data: {
+                            let d = alloc::alloc_or_abort(capacity);
+                            unsafe {
+                                ptr::copy_nonoverlapping(item.as_ptr(), d, len);
+                            }
+                            d
+                        },
+                        align_shift: 0
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl From<std::boxed::Box<str>> for String {
+    #[inline]
+    fn from(item: std::boxed::Box<str>) -> String {
+        let len = item.len();
+        String {
+            len,
+            inner: Inner::Heap {
+                capacity: len,
+                data: std::boxed::Box::into_raw(item) as *mut u8,
+                align_shift: 0
+            }
+        }
+    }
+}
+
+impl<'a> From<Cow<'a, str>> for String {
+    /// Converts a clone-on-write string into a `String`.
+    ///
+    /// An owned string hands its buffer over without copying, as in
+    /// [`String::from_string`], while a borrowed one is copied.
+    ///
+    /// [`String::from_string`]: struct.String.html#method.from_string
+    #[inline]
+    fn from(item: Cow<'a, str>) -> String {
+        match item {
+            Cow::Borrowed(s) => String::from(s),
+            Cow::Owned(s) => String::from_string(s)
+        }
+    }
+}
+
+impl<'a> From<String> for Cow<'a, str> {
+    /// Converts a `String` into the [`Owned`] variant of a clone-on-write
+    /// string, without copying a string on the heap.
+    ///
+    /// [`Owned`]: https://doc.rust-lang.org/nightly/std/borrow/enum.Cow.html#variant.Owned
+    #[inline]
+    fn from(item: String) -> Cow<'a, str> {
+        Cow::Owned(item.into_string())
+    }
+}
+
+impl From<char> for String {
+    #[inline]
+    fn from(c: char) -> String {
+        let mut s = String::new();
+        s.push(c);
+        s
+    }
+}
+
+impl From<String> for std::boxed::Box<[u8]> {
+    /// Converts a `String` into a boxed byte slice.
+    ///
+    /// A string on the heap is shrunk to fit and hands its allocation over,
+    /// while a string on the stack is copied into an allocation of exactly
+    /// its length. See [`String::into_boxed_str`].
+    ///
+    /// [`String::into_boxed_str`]: struct.String.html#method.into_boxed_str
+    #[inline]
+    fn from(item: String) -> std::boxed::Box<[u8]> {
+        std::boxed::Box::from(item.into_boxed_str())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::net::ToSocketAddrs for String {
+    type Iter = std::option::IntoIter<std::net::SocketAddr>;
+    #[inline]
+    fn to_socket_addrs(&self) -> std::io::Result<Self::Iter> {
+        (&self).to_socket_addrs()
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl From<String> for ::bytes::Bytes {
+    /// Converts a `String` into `Bytes`.
+    ///
+    /// A string on the heap hands its allocation over without copying, while
+    /// a string on the stack is copied into a new allocation.
+    #[inline]
+    fn from(item: String) -> ::bytes::Bytes {
+        match item.inner {
+            Inner::Stack { .. } => ::bytes::Bytes::copy_from_slice(item.as_bytes()),
+            Inner::Heap { .. } => ::bytes::Bytes::from(item.into_bytes())
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum ParseError {}
+
+impl std::str::FromStr for String {
+    type Err = ParseError;
+    #[inline]
+    fn from_str(s: &str) -> Result<String, ParseError> {
+        Ok(String::from(s))
+    }
+}
+
+impl<'a> std::ops::Add<&'a str> for String {
+    type Output = String;
+    #[inline]
+    fn add(mut self, other: &'a str) -> String {
+        self.push_str(other);
+        self
+    }
+}
+
+impl<'a> std::ops::AddAssign<&'a str> for String {
+    #[inline]
+    fn add_assign(&mut self, rhs: &'a str) {
+        self.push_str(rhs);
+    }
+}
+
+
+impl Extend<char> for String {
+    fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+        let iterator = iter.into_iter();
+        let (lower_bound, _) = iterator.size_hint();
+        self.reserve(lower_bound);
+        for ch in iterator {
+            self.push(ch)
+        }
+    }
+}
+
+impl<'a> Extend<&'a char> for String {
+    fn extend<I: IntoIterator<Item = &'a char>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().cloned());
+    }
+}
+
+impl<'a> Extend<&'a str> for String {
+    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, iter: I) {
+        for s in iter {
+            self.push_str(s)
+        }
+    }
+}
+
+impl Extend<String> for String {
+    fn extend<I: IntoIterator<Item = String>>(&mut self, iter: I) {
+        for s in iter {
+            self.push_str(&s)
+        }
+    }
+}
+
+impl<'a> Extend<&'a String> for String {
+    fn extend<I: IntoIterator<Item = &'a String>>(&mut self, iter: I) {
+        for s in iter {
+            self.push_str(s.as_str())
+        }
+    }
+}
+
+impl std::iter::FromIterator<char> for String {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> String {
+        let mut buf = String::new();
+        buf.extend(iter);
+        buf
+    }
+}
+
+impl<'a> std::iter::FromIterator<&'a char> for String {
+    fn from_iter<I: IntoIterator<Item = &'a char>>(iter: I) -> String {
+        let mut buf = String::new();
+        buf.extend(iter);
+        buf
+    }
+}
+
+impl<'a> std::iter::FromIterator<&'a str> for String {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> String {
+        let mut buf = String::new();
+        buf.extend(iter);
+        buf
+    }
+}
+
+impl std::iter::FromIterator<String> for String {
+    fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> String {
+        let mut buf = String::new();
+        buf.extend(iter);
+        buf
+    }
+}
+
+impl<'a> std::iter::FromIterator<&'a String> for String {
+    fn from_iter<I: IntoIterator<Item = &'a String>>(iter: I) -> String {
+        let mut buf = String::new();
+        buf.extend(iter);
+        buf
+    }
+}
+
+impl PartialEq for String {
+    #[inline]
+    fn eq(&self, rhs: &Self) -> bool {
+        self.as_str() == rhs.as_str()
+    }
+}
+impl Eq for String { }
+
+impl PartialEq<String> for str {
+    #[inline]
+    fn eq(&self, rhs: &String) -> bool {
+        self == rhs.as_str()
+    }
+}
+impl PartialEq<str> for String {
+    #[inline]
+    fn eq(&self, rhs: &str) -> bool {
+        self.as_str() == rhs
+    }
+}
+impl<'a> PartialEq<&'a str> for String {
+    #[inline]
+    fn eq(&self, rhs: &&'a str) -> bool {
+        &self.as_str() == rhs
+    }
+}
+impl<'a> PartialEq<String> for &'a str {
+    #[inline]
+    fn eq(&self, rhs: &String) -> bool {
+        self == &rhs.as_str()
+    }
+}
+impl PartialEq<std::string::String> for String {
+    #[inline]
+    fn eq(&self, rhs: &std::string::String) -> bool {
+        self.as_str() == rhs.as_str()
+    }
+}
+impl PartialEq<String> for std::string::String {
+    #[inline]
+    fn eq(&self, rhs: &String) -> bool {
+        self.as_str() == rhs.as_str()
+    }
+}
+impl PartialEq<&std::string::String> for String {
+    #[inline]
+    fn eq(&self, rhs: &&std::string::String) -> bool {
+        self.as_str() == rhs.as_str()
+    }
+}
+impl PartialEq<String> for &std::string::String {
+    #[inline]
+    fn eq(&self, rhs: &String) -> bool {
+        self.as_str() == rhs.as_str()
+    }
+}
+impl PartialEq<std::string::String> for &String {
+    #[inline]
+    fn eq(&self, rhs: &std::string::String) -> bool {
+        self.as_str() == rhs.as_str()
+    }
+}
+impl PartialEq<&String> for std::string::String {
+    #[inline]
+    fn eq(&self, rhs: &&String) -> bool {
+        self.as_str() == rhs.as_str()
+    }
+}
+
+impl PartialEq<char> for String {
+    #[inline]
+    fn eq(&self, rhs: &char) -> bool {
+        self.eq_char(*rhs)
+    }
+}
+
+impl PartialOrd for String {
+    #[inline]
+    fn partial_cmp(&self, rhs: &Self) -> Option<::std::cmp::Ordering> {
+        self.as_str().partial_cmp(rhs.as_str())
+    }
+}
+impl PartialOrd<str> for String {
+    #[inline]
+    fn partial_cmp(&self, rhs: &str) -> Option<::std::cmp::Ordering> {
+        self.as_str().partial_cmp(rhs)
+    }
+}
+impl<'a> PartialOrd<&'a str> for String {
+    #[inline]
+    fn partial_cmp(&self, rhs: &&'a str) -> Option<::std::cmp::Ordering> {
+        self.as_str().partial_cmp(*rhs)
+    }
+}
+impl PartialOrd<String> for str {
+    #[inline]
+    fn partial_cmp(&self, rhs: &String) -> Option<::std::cmp::Ordering> {
+        self.partial_cmp(rhs.as_str())
+    }
+}
+impl PartialOrd<String> for &str {
+    #[inline]
+    fn partial_cmp(&self, rhs: &String) -> Option<::std::cmp::Ordering> {
+        (*self).partial_cmp(rhs.as_str())
+    }
+}
+impl PartialOrd<char> for String {
+    #[inline]
+    fn partial_cmp(&self, rhs: &char) -> Option<::std::cmp::Ordering> {
+        let mut bits = [0; 4];
+        self.as_str().partial_cmp(&*rhs.encode_utf8(&mut bits))
+    }
+}
+impl Ord for String {
+    #[inline]
+    fn cmp(&self, rhs: &Self) -> ::std::cmp::Ordering {
+        self.as_str().cmp(rhs.as_str())
+    }
+}
+
+impl std::fmt::Write for String {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        Ok(self.push_str(s))
+    }
+
+    fn write_char(&mut self, c: char) -> std::fmt::Result {
+        Ok(self.push(c))
+    }
+
+    #[inline]
+    fn write_fmt(&mut self, args: std::fmt::Arguments) -> std::fmt::Result {
+        if let Some(s) = args.as_str() {
+            return self.write_str(s);
+        }
+        // Reserving ahead does not save allocations here, as `push_str`
+        // already grows geometrically (see benches/write_fmt.rs)
+        std::fmt::write(self, args)
+    }
+}
+
+impl std::fmt::Display for String {
+    #[inline]
+    fn fmt(&self, fm: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        (self as &str).fmt(fm)
+    }
+}
+
+impl Drop for String {
+    #[inline]
+    fn drop(&mut self) {
+        if let Inner::Heap { capacity, data, align_shift } = &self.inner {
+            if *capacity > 0 {
+                unsafe { String::dealloc_heap(*data, *capacity, *align_shift) };
+            }
+        }
+    }
+}
+
+/// The adapter returned by [`String::display_truncated`].
+///
+/// [`String::display_truncated`]: struct.String.html#method.display_truncated
+struct Truncated<'a> {
+    s: &'a str,
+    max_chars: usize
+}
+
+impl<'a> fmt::Display for Truncated<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cut = self.s.char_indices().nth(self.max_chars).map(|(idx, _)| idx);
+        if f.width().is_none() && f.precision().is_none() {
+            return match cut {
+                Some(idx) => {
+                    f.write_str(&self.s[..idx])?;
+                    f.write_str("…")
+                },
+                None => f.write_str(self.s)
+            };
+        }
+        // Padding needs the whole output at once
+        match cut {
+            Some(idx) => {
+                let mut truncated = String::from(&self.s[..idx]);
+                truncated.push('…');
+                f.pad(&truncated)
+            },
+            None => f.pad(self.s)
+        }
+    }
+}
+
+///
+/// A `String` wrapper that compares and hashes ignoring ASCII case.
+///
+/// This allows `CaseFold` to be used as the key of a `HashMap` for
+/// case-insensitive lookups. Hashing folds each byte to lowercase as it is fed
+/// to the hasher, so no lowercased copy of the string is allocated.
+///
+/// # Examples
+///
+/// Basic usage:
+/// ```
+/// # extern crate small;
+/// use small::string::CaseFold;
+/// use std::collections::HashMap;
+///
+/// let mut headers = HashMap::new();
+/// headers.insert(CaseFold("Host".into()), "example.com");
+///
+/// assert_eq!(Some(&"example.com"), headers.get(&CaseFold("HOST".into())));
+/// ```
+///
+#[derive(Clone, Debug)]
+pub struct CaseFold(pub String);
+
+impl PartialEq for CaseFold {
+    #[inline]
+    fn eq(&self, rhs: &Self) -> bool {
+        self.0.eq_ignore_ascii_case(&rhs.0)
+    }
+}
+impl Eq for CaseFold { }
+
+impl std::hash::Hash for CaseFold {
+    #[inline]
+    fn hash<H: std::hash::Hasher>(&self, hs: &mut H) {
+        for byte in self.0.bytes() {
+            hs.write_u8(byte.to_ascii_lowercase());
+        }
+        // Mirror `str`'s hash so that "ab" + "c" and "a" + "bc" differ
+        hs.write_u8(0xff);
+    }
+}
+
+/// A draining iterator for `String`.
+///
+/// This struct is created by the [`drain`] method on [`String`]. See its
+/// documentation for more.
+///
+/// [`drain`]: struct.String.html#method.drain
+/// [`String`]: struct.String.html
+pub struct Drain<'a> {
+    /// Will be used as &'a mut String in the destructor
+    string: *mut String,
+    /// Start of part to remove
+    start: usize,
+    /// End of part to remove
+    end: usize,
+    /// Current remaining range to remove
+    iter: std::str::Chars<'a>
+}
+
+impl<'a> Drain<'a> {
+    /// Returns the remaining (sub)string of this iterator as a slice.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.iter.as_str()
+    }
+}
+
+impl<'a> fmt::Debug for Drain<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Drain").field(&self.as_str()).finish()
+    }
+}
+
+impl<'a> Drop for Drain<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            let string = &mut *self.string;
+            let tail = string.len - self.end;
+            ::std::ptr::copy(string.as_ptr().add(self.end),
+                             string.as_mut_ptr().add(self.start),
+                             tail);
+            string.len = self.start + tail;
+        }
+    }
+}
+
+impl<'a> Iterator for Drain<'a> {
+    type Item = char;
+
+    #[inline]
+    fn next(&mut self) -> Option<char> {
+        self.iter.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Drain<'a> {
+    #[inline]
+    fn next_back(&mut self) -> Option<char> {
+        self.iter.next_back()
+    }
+}
+
+impl<'a> std::iter::FusedIterator for Drain<'a> { }
+
+/// The error returned by [`String::try_reserve`].
+///
+/// [`String::try_reserve`]: struct.String.html#method.try_reserve
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryReserveError {
+    /// The requested capacity exceeds the maximum size of an allocation
+    CapacityOverflow,
+    /// The allocator failed to provide a buffer of `capacity` bytes
+    AllocError {
+        capacity: usize
+    }
+}
+
+impl std::fmt::Display for TryReserveError {
+    fn fmt(&self, fm: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            TryReserveError::CapacityOverflow => fm.write_str("capacity overflow"),
+            TryReserveError::AllocError { capacity } => {
+                write!(fm, "memory allocation of {} bytes failed", capacity)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TryReserveError {}
+
+#[derive(Debug)]
+pub struct FromUtf8Error {
+    bytes: std::vec::Vec<u8>,
+    error: std::str::Utf8Error,
+}
+impl FromUtf8Error {
+    /// Returns a slice of [`u8`]s bytes that were attempted to convert to a `String`.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// // some invalid bytes, in a vector
+    /// let bytes = vec![0, 159];
+    ///
+    /// let value = String::from_utf8(bytes);
+    ///
+    /// assert_eq!(&[0, 159], value.unwrap_err().as_bytes());
+    /// ```
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..]
+    }
+
+    /// Returns the bytes that were attempted to convert to a `String`.
+    ///
+    /// This method is carefully constructed to avoid allocation. It will
+    /// consume the error, moving out the bytes, so that a copy of the bytes
+    /// does not need to be made.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// // some invalid bytes, in a vector
+    /// let bytes = vec![0, 159];
+    ///
+    /// let value = String::from_utf8(bytes);
+    ///
+    /// assert_eq!(vec![0, 159], value.unwrap_err().into_bytes());
+    /// ```
+    #[inline]
+    pub fn into_bytes(self) -> std::vec::Vec<u8> {
+        self.bytes
+    }
+
+    /// Fetch a `Utf8Error` to get more details about the conversion failure.
+    ///
+    /// The [`Utf8Error`] type provided by [`std::str`] represents an error that may
+    /// occur when converting a slice of [`u8`]s to a [`&str`]. In this sense, it's
+    /// an analogue to `FromUtf8Error`. See its documentation for more details
+    /// on using it.
+    ///
+    /// [`Utf8Error`]: ../../std/str/struct.Utf8Error.html
+    /// [`std::str`]: ../../std/str/index.html
+    /// [`u8`]: ../../std/primitive.u8.html
+    /// [`&str`]: ../../std/primitive.str.html
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # extern crate small;
+    /// use small::String;
+    /// // some invalid bytes, in a vector
+    /// let bytes = vec![0, 159];
+    ///
+    /// let error = String::from_utf8(bytes).unwrap_err().utf8_error();
+    ///
+    /// // the first byte is invalid here
+    /// assert_eq!(1, error.valid_up_to());
+    /// ```
+    #[inline]
+    pub fn utf8_error(&self) -> std::str::Utf8Error {
+        self.error
+    }
+}
+
+/// A possible error value when converting a `String` from a UTF-16 byte slice.
+///
+/// This type is the error type for the [`from_utf16`] method on [`String`].
+///
+/// [`from_utf16`]: struct.String.html#method.from_utf16
+/// [`String`]: struct.String.html
+///
+/// # Examples
+///
+/// Basic usage:
+///
+/// ```
+/// # extern crate small;
+/// use small::String;
+/// // 𝄞mu<invalid>ic
+/// let v = &[0xD834, 0xDD1E, 0x006d, 0x0075,
+///           0xD800, 0x0069, 0x0063];
+///
+/// assert!(String::from_utf16(v).is_err());
+/// ```
+#[derive(Debug)]
+pub struct FromUtf16Error(());
+
+impl std::fmt::Display for FromUtf16Error {
+    fn fmt(&self, fm: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fm.write_str("invalid utf-16: lone surrogate found")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FromUtf16Error {}
+
+#[cfg(all(feature = "serde", feature = "std"))]
+impl Serialize for String {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self)
+    }
+}
+
+#[cfg(all(feature = "serde", feature = "std"))]
+struct StringVisitor;
+
+#[cfg(all(feature = "serde", feature = "std"))]
+struct StringInPlaceVisitor<'a>(&'a mut String);
+
+#[cfg(all(feature = "serde", feature = "std"))]
+impl<'de> Visitor<'de> for StringVisitor {
+    type Value = String;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(String::from(v.to_owned()))
+    }
+
+    fn visit_string<E>(self, v: std::string::String) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(String::from_string(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        use std::str;
+        match str::from_utf8(v) {
+            Ok(s) => Ok(String::from(s.to_owned())),
+            Err(_) => Err(Error::invalid_value(Unexpected::Bytes(v), &self)),
+        }
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        match String::from_utf8(v) {
+            Ok(s) => Ok(s),
+            Err(e) => Err(Error::invalid_value(
+                Unexpected::Bytes(&e.into_bytes()),
+                &self,
+            )),
+        }
+    }
+}
+
+#[cfg(all(feature = "serde", feature = "std"))]
+impl<'a, 'de> Visitor<'de> for StringInPlaceVisitor<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        self.0.clear();
+        self.0.push_str(v);
+        Ok(())
+    }
+
+    fn visit_string<E>(self, v: std::string::String) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        *self.0 = String::from_string(v);
+        Ok(())
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        use std::str;
+        match str::from_utf8(v) {
+            Ok(s) => {
+                self.0.clear();
+                self.0.push_str(s);
+                Ok(())
+            }
+            Err(_) => Err(Error::invalid_value(Unexpected::Bytes(v), &self)),
+        }
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        match String::from_utf8(v) {
+            Ok(s) => {
+                *self.0 = s;
+                Ok(())
+            }
+            Err(e) => Err(Error::invalid_value(
+                Unexpected::Bytes(&e.into_bytes()),
+                &self,
+            )),
+        }
+    }
+}
+
+#[cfg(all(feature = "serde", feature = "std"))]
+impl<'de> Deserialize<'de> for String {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_string(StringVisitor)
+    }
+
+    fn deserialize_in_place<D>(deserializer: D, place: &mut Self) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_string(StringInPlaceVisitor(place))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn str_under_24() {
+        assert_eq!("hello", String::from("hello"))
+    }
+    #[test]
+    fn str_over_24() {
+        assert_eq!("abcdefghijklmnopqrstuvwxyz",
+                   String::from("abcdefghijklmnopqrstuvwxyz"))
+    }
+    #[test]
+    fn sort() {
+        let mut v = vec![
+            String::from("c"), String::from("b"), String::from("a"),
+            String::from("d"), String::from("e")
+        ];
+        v.sort();
+        assert_eq!(v,
+                   [String::from("a"), String::from("b"),
+                    String::from("c"), String::from("d"),
+                    String::from("e")]);
+    }
+    #[test]
+    fn clone_stack() {
+        let a = super::String::from("hello");
+        assert_eq!(a.clone(), a);
+    }
+    #[test]
+    fn clone_heap() {
+        let a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        assert_eq!(a.clone(), a);
+    }
+    #[test]
+    fn push_stack() {
+        let mut a = super::String::from("hell");
+        a.push('o');
+        assert_eq!("hello", a)
+    }
+    #[test]
+    fn push_stack_to_heap() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvw");
+        a.push('x');
+        assert_eq!("abcdefghijklmnopqrstuvwx", a)
+    }
+    #[test]
+    fn push_heap() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxy");
+        a.push('z');
+        assert_eq!("abcdefghijklmnopqrstuvwxyz", a)
+    }
+    #[test]
+    fn push_str_stack() {
+        let mut a = super::String::from("h");
+        a.push_str("ello");
+        assert_eq!("hello", a)
+    }
+    #[test]
+    fn push_str_heap() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.push_str(" hello");
+        assert_eq!("abcdefghijklmnopqrstuvwxyz hello", a)
+    }
+    #[test]
+    fn push_str_stack_to_heap() {
+        let mut a = super::String::from("abcdefghijkl");
+        a.push_str("mnopqrstuvwxyz hello");
+        assert_eq!("abcdefghijklmnopqrstuvwxyz hello", a)
+    }
+    #[test]
+    fn grow_heap() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.push_str(" hello thing");
+        assert_eq!("abcdefghijklmnopqrstuvwxyz hello thing", a)
+    }
+    #[test]
+    fn into_bytes_stack() {
+        let a = super::String::from("hello");
+        assert_eq!(a.into_bytes(), vec![104, 101, 108, 108, 111])
+    }
+    #[test]
+    fn into_bytes_heap() {
+        let a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        assert_eq!(a.into_bytes(), vec![97, 98, 99, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 116, 117, 118, 119, 120, 121, 122])
+    }
+    #[test]
+    fn clear_heap() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        let original_capacity = a.capacity();
+        a.clear();
+        assert_eq!(a, "");
+        assert_eq!(a.capacity(), original_capacity);
+    }
+    #[test]
+    fn retain_into() {
+        let a = super::String::from("a_b_c");
+        assert_eq!(a.retain_into(|c| c != '_'), "abc");
+        assert_eq!(a, "a_b_c");
+    }
+    #[test]
+    fn byte_at() {
+        let a = super::String::from("hi");
+        assert_eq!(a.byte_at(0), Some(104));
+        assert_eq!(a.byte_at(10), None);
+    }
+    #[test]
+    fn ignore_ascii_case_affixes() {
+        let a = super::String::from("IMAGE.PNG");
+        assert!(a.ends_with_ignore_ascii_case(".png"));
+        assert!(a.starts_with_ignore_ascii_case("image"));
+        assert!(!a.ends_with_ignore_ascii_case("a longer suffix than the string"));
+    }
+    #[cfg(feature = "std")]
+    #[test]
+    fn case_fold_hash_map() {
+        let mut map = ::std::collections::HashMap::new();
+        map.insert(CaseFold("Host".into()), 1);
+        assert_eq!(map.get(&CaseFold("HOST".into())), Some(&1));
+        assert_eq!(map.get(&CaseFold("Hosts".into())), None);
+    }
+    #[test]
+    fn split_whitespace_owned() {
+        let a = super::String::from("  a  bb  ");
+        let tokens: Vec<_> = a.split_whitespace_owned().collect();
+        assert_eq!(tokens, [String::from("a"), String::from("bb")]);
+        assert!(!tokens[0].overflowed());
+    }
+    #[test]
+    fn push_all() {
+        let mut a = super::String::new();
+        a.push_all(["a", "b", "c"].iter().cloned());
+        assert_eq!(a, "abc");
+        a.push_all(::std::iter::empty());
+        assert_eq!(a, "abc");
+    }
+    #[test]
+    fn push_all_long_first() {
+        let first = "a".repeat(1024);
+        let mut a = super::String::new();
+        a.push_all(::std::iter::once(&*first).chain(vec!["b"; 1000]));
+        assert_eq!(a.len(), 2024);
+        assert!(a.capacity() <= 4096);
+    }
+    #[test]
+    fn as_parts() {
+        let a = super::String::from("hello");
+        assert_eq!(a.as_parts(), Storage::Inline(b"hello"));
+        let a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        assert_eq!(a.as_parts(), Storage::Heap {
+            capacity: 32,
+            bytes: b"abcdefghijklmnopqrstuvwxyz"
+        });
+    }
+    #[test]
+    fn collect_result() {
+        let ok: Result<super::String, ()> = vec![Ok('a'), Ok('b')].into_iter().collect();
+        assert_eq!(ok, Ok(super::String::from("ab")));
+        let err: Result<super::String, ()> = vec![Ok('a'), Err(()), Ok('b')].into_iter().collect();
+        assert_eq!(err, Err(()));
+    }
+    #[test]
+    fn substr_mut() {
+        let mut a = super::String::from("abcd");
+        a.substr_mut(1..3).unwrap().make_ascii_uppercase();
+        assert_eq!(a, "aBCd");
+        assert!(a.substr_mut(3..=4).is_none());
+        let mut a = super::String::from("💖");
+        assert!(a.substr_mut(1..).is_none());
+    }
+    #[test]
+    fn count_byte() {
+        let a = super::String::from("id,name,,email,\u{80},phone,address,city,zip,country,notes,,");
+        let naive = a.bytes().filter(|&b| b == b',').count();
+        assert_eq!(a.count_byte(b','), naive);
+        assert_eq!(a.count_byte(0x80), 1);
+        assert_eq!(a.count_byte(b'!'), 0);
+    }
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn into_bytes_crate() {
+        let a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        let ptr = a.as_ptr();
+        let b = ::bytes::Bytes::from(a);
+        assert_eq!(&b[..], b"abcdefghijklmnopqrstuvwxyz");
+        assert_eq!(b.as_ptr(), ptr);
+        let b = ::bytes::Bytes::from(super::String::from("hello"));
+        assert_eq!(&b[..], b"hello");
+    }
+    #[test]
+    fn line_offsets() {
+        let a = super::String::from("ab\r\n\ncd\n");
+        let lines: Vec<_> = a.line_offsets().collect();
+        assert_eq!(lines, [(0, "ab"), (4, ""), (5, "cd")]);
+    }
+    #[test]
+    fn retain_and_compact() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.retain_and_compact(|_| false);
+        assert_eq!(a, "");
+        assert!(!a.overflowed());
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.retain_and_compact(|c| c != 'a');
+        assert_eq!(a, "bcdefghijklmnopqrstuvwxyz");
+        assert!(a.overflowed());
+    }
+    #[test]
+    fn write_fmt() {
+        use std::fmt::Write;
+        let mut a = super::String::new();
+        write!(a, "{}-{}", 1, 2).unwrap();
+        assert_eq!(a, "1-2");
+        assert!(!a.overflowed());
+        write!(a, "abcdefghijklmnopqrstuvwxyz").unwrap();
+        write!(a, "{} {:?}", 3.5, "four").unwrap();
+        assert_eq!(a, "1-2abcdefghijklmnopqrstuvwxyz3.5 \"four\"");
+        assert_eq!(a.capacity(), 64);
+    }
+    // The tests below exercise zero-length buffers, where the heap pointer
+    // may be dangling. They are most useful when run under Miri.
+    fn check_empty(a: super::String) {
+        assert_eq!(a.as_str(), "");
+        assert_eq!(a.as_bytes(), b"");
+        assert_eq!(&*a, "");
+        assert_eq!(a.len(), 0);
+    }
+    #[test]
+    fn zero_length_stack() {
+        check_empty(super::String::new());
+    }
+    #[test]
+    fn zero_length_heap() {
+        let a = super::String::from_utf8(vec![]).unwrap();
+        assert!(a.overflowed());
+        check_empty(a);
+        let mut a = super::String::from_string(::std::string::String::new());
+        a.push('a');
+        a.push_str("bc");
+        assert_eq!(a, "abc");
+    }
+    #[test]
+    fn zero_length_cleared() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.clear();
+        check_empty(a);
+    }
+    #[test]
+    fn zero_length_shrunk() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.clear();
+        a.shrink_to_fit();
+        assert!(!a.overflowed());
+        a.shrink_to_fit();
+        check_empty(a.clone());
+        a.push_str("hello");
+        assert_eq!(a, "hello");
+    }
+    #[test]
+    fn set_len() {
+        let mut a = super::String::with_capacity(32);
+        let mut n = 9876543210u64;
+        let mut digits = 10;
+        unsafe {
+            let ptr = a.as_mut_ptr();
+            while n > 0 {
+                digits -= 1;
+                *ptr.add(digits) = b'0' + (n % 10) as u8;
+                n /= 10;
+            }
+            a.set_len(10);
+        }
+        assert_eq!(digits, 0);
+        assert_eq!(a, "9876543210");
+    }
+    #[test]
+    fn reserve_or_inline() {
+        let mut a = super::String::new();
+        assert!(!a.reserve_or_inline(10));
+        assert!(!a.overflowed());
+        assert!(a.reserve_or_inline(30));
+        assert!(a.overflowed());
+        assert!(a.reserve_or_inline(0));
+    }
+    #[test]
+    fn eq_std_string() {
+        let small = super::String::from("hello");
+        let std = ::std::string::String::from("hello");
+        assert!(small == std);
+        assert!(std == small);
+        // Written with assert_eq!, as clippy flags `==` written out between a
+        // reference and a value
+        assert_eq!(small, &std);
+        assert_eq!(&std, small);
+        assert_eq!(&small, std);
+        assert_eq!(std, &small);
+        assert!(*"hello" == std && *"hello" == small);
+        assert!("hello" == std && "hello" == small);
+        assert!(std == *"hello" && small == *"hello");
+        assert!(std == "hello" && small == "hello");
+        assert_ne!(small, ::std::string::String::from("world"));
+        let small = super::String::from("a string long enough for the heap");
+        let std = ::std::string::String::from("a string long enough for the heap");
+        assert_eq!(small, std);
+        assert_eq!(std, small);
+        assert_ne!(super::String::from("hello"), std);
+        assert_ne!(std, super::String::from("hello"));
+    }
+    #[test]
+    fn pop_str() {
+        let mut a = super::String::from("a€b€");
+        assert_eq!(a.pop_str(0), "");
+        assert_eq!(a.pop_str(2), "b€");
+        assert_eq!(a, "a€");
+        assert_eq!(a.pop_str(3), "a€");
+        assert_eq!(a, "");
+    }
+    #[test]
+    fn utf16_len() {
+        let a = super::String::from("a𐐷b");
+        assert_eq!(a.utf16_len(), 4);
+        assert_eq!(a.utf16_len(), a.encode_utf16().count());
+    }
+    #[test]
+    fn byte_affixes() {
+        let a = super::String::from("héllo");
+        assert!(a.starts_with_bytes(&[b'h', 0xc3]));
+        assert!(!a.starts_with_bytes(&[b'h', 0xa9]));
+        assert!(a.ends_with_bytes(b"llo"));
+        assert!(!a.ends_with_bytes(b"xhello"));
+    }
+    #[test]
+    fn display_truncated() {
+        let a = super::String::from("abcdef");
+        assert_eq!(format!("{}", a.display_truncated(3)), "abc…");
+        assert_eq!(format!("{}", a.display_truncated(0)), "…");
+        assert_eq!(format!("{}", a.display_truncated(10)), "abcdef");
+        assert_eq!(format!("{:>10}", a.display_truncated(3)), "      abc…");
+        assert_eq!(format!("{:-<6}", a.display_truncated(3)), "abc…--");
+        assert_eq!(format!("{:^8}", a.display_truncated(10)), " abcdef ");
+        assert_eq!(format!("{:.2}", a.display_truncated(3)), "ab");
+    }
+    #[test]
+    fn swap() {
+        let mut a = super::String::from("hello");
+        let mut b = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.swap(&mut b);
+        assert_eq!(a, "abcdefghijklmnopqrstuvwxyz");
+        assert!(a.overflowed());
+        assert_eq!(b, "hello");
+        assert!(!b.overflowed());
+    }
+    #[test]
+    fn reserve_returning() {
+        let mut a = super::String::new();
+        assert_eq!(a.reserve_returning(30), 32);
+        assert!(a.overflowed());
+        assert_eq!(a.reserve_returning(0), 32);
+    }
+    #[test]
+    fn contains_char() {
+        let a = super::String::from("the quick brown fox jumps over the lazy dog, twice");
+        assert!(a.contains_char(','));
+        assert!(a.contains_char('e'));
+        assert!(!a.contains_char('!'));
+        assert!(!a.contains_char('é'));
+        assert_eq!(a.find_byte(b'q'), Some(4));
+        assert_eq!(a.find_byte(b','), Some(43));
+        assert_eq!(a.find_byte(b'e'), Some(2));
+        assert_eq!(a.find_byte(b'!'), None);
+        let a = super::String::from("naïve café");
+        assert!(a.contains_char('é'));
+    }
+    #[test]
+    fn ensure_heap() {
+        let mut a = super::String::from("hello");
+        a.ensure_heap();
+        assert!(a.overflowed());
+        assert_eq!(a, "hello");
+        let ptr = a.as_ptr();
+        a.ensure_heap();
+        assert_eq!(a.as_ptr(), ptr);
+    }
+    #[test]
+    fn replace_cow() {
+        let a = super::String::from("a-b-c");
+        match a.replace_cow('_', ' ') {
+            Cow::Borrowed(s) => assert_eq!(s, "a-b-c"),
+            Cow::Owned(_) => panic!("expected a borrowed string"),
+        }
+        match a.replace_cow('-', '€') {
+            Cow::Owned(s) => assert_eq!(s, "a€b€c"),
+            Cow::Borrowed(_) => panic!("expected an owned string"),
+        }
+    }
+    #[test]
+    fn split_at_mut() {
+        let mut a = super::String::from("abcdefghijklmnopQRSTUVWXYZ");
+        {
+            let (first, last) = a.split_at_mut(13);
+            first.make_ascii_uppercase();
+            last.make_ascii_lowercase();
+        }
+        assert_eq!(a, "ABCDEFGHIJKLMnopqrstuvwxyz");
+    }
+    #[test]
+    #[should_panic]
+    fn split_at_mut_not_char_boundary() {
+        let mut a = super::String::from("a€");
+        a.split_at_mut(2);
+    }
+    #[test]
+    fn reserve_zero() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvw");
+        assert_eq!(a.len(), 23);
+        a.reserve(0);
+        assert!(!a.overflowed());
+        a.reserve(1);
+        assert!(a.overflowed());
+    }
+    #[test]
+    fn spare_capacity_mut() {
+        let mut a = super::String::new();
+        unsafe {
+            let spare = a.spare_capacity_mut();
+            assert_eq!(spare.len(), 23);
+            spare[..2].copy_from_slice(b"42");
+            a.set_len(2);
+        }
+        assert_eq!(a, "42");
+        let mut a = super::String::from("abcdefghijklmnopqrstuvw");
+        unsafe {
+            let spare = a.spare_capacity_mut();
+            assert_eq!(spare.len(), 9);
+            spare[0] = b'x';
+            a.set_len(24);
+        }
+        assert!(a.overflowed());
+        assert_eq!(a, "abcdefghijklmnopqrstuvwx");
+    }
+    #[cfg(feature = "std")]
+    #[test]
+    fn arc_hash_map_key() {
+        use std::sync::Arc;
+        let mut map = ::std::collections::HashMap::new();
+        map.insert(Arc::new(super::String::from("key")), 1);
+        assert_eq!(map.get(&super::String::from("key")), Some(&1));
+        assert_eq!(map.get(&super::String::from("other")), None);
+        let mut map = ::std::collections::HashMap::new();
+        map.insert(super::String::from("key"), 1);
+        assert_eq!(map.get("key"), Some(&1));
+    }
+    #[test]
+    fn from_string_compact() {
+        let a = super::String::from_string_compact("hello".into());
+        assert_eq!(a, "hello");
+        assert!(!a.overflowed());
+        let b: ::std::string::String = "abcdefghijklmnopqrstuvwxyz".into();
+        let ptr = b.as_ptr();
+        let a = super::String::from_string_compact(b);
+        assert_eq!(a.as_ptr(), ptr);
+    }
+    #[test]
+    fn push_str_counted() {
+        let mut a = super::String::new();
+        assert_eq!(a.push_str_counted("abcdef"), 6);
+        assert_eq!(a.push_str_counted("€"), 3);
+        assert_eq!(a, "abcdef€");
+    }
+    #[test]
+    fn empty_const() {
+        struct Named {
+            name: super::String
+        }
+        const NAMED: Named = Named { name: super::String::EMPTY };
+        let mut named = NAMED;
+        assert_eq!(named.name, "");
+        assert!(!named.name.overflowed());
+        named.name.push_str("hello");
+        assert_eq!(named.name, "hello");
+    }
+    #[test]
+    fn shrink_to_fit_fitted() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.shrink_to_fit();
+        assert_eq!(a.capacity(), 26);
+        let ptr = a.as_ptr();
+        a.shrink_to_fit();
+        assert_eq!(a.as_ptr(), ptr);
+        assert_eq!(a, "abcdefghijklmnopqrstuvwxyz");
+    }
+    #[test]
+    fn from_utf8_unchecked_compact() {
+        let a = unsafe { super::String::from_utf8_unchecked_compact(vec![104, 101, 108, 108, 111]) };
+        assert_eq!(a, "hello");
+        assert!(!a.overflowed());
+        let v = b"abcdefghijklmnopqrstuvwxyz".to_vec();
+        let ptr = v.as_ptr();
+        let a = unsafe { super::String::from_utf8_unchecked_compact(v) };
+        assert_eq!(a.as_ptr(), ptr);
+    }
+    #[test]
+    fn from_boxed_str() {
+        let b: Box<str> = Box::from("abcdefghijklmnopqrstuvwxyz");
+        let ptr = b.as_ptr();
+        let mut a = super::String::from(b);
+        assert_eq!(a, "abcdefghijklmnopqrstuvwxyz");
+        assert_eq!(a.as_ptr(), ptr);
+        assert_eq!(a.capacity(), 26);
+        a.push('!');
+        assert_eq!(a, "abcdefghijklmnopqrstuvwxyz!");
+        let a = super::String::from(Box::<str>::from(""));
+        assert_eq!(a, "");
+    }
+    #[test]
+    fn extend_runs() {
+        let mut a = super::String::new();
+        a.extend_runs(vec![('a', 3), ('b', 2), ('c', 0)]);
+        assert_eq!(a, "aaabb");
+        a.extend_runs(vec![('€', 10)]);
+        assert_eq!(a, "aaabb€€€€€€€€€€");
+    }
+    #[test]
+    fn with_capacity_zero() {
+        let a = super::String::with_capacity(0);
+        assert!(!a.overflowed());
+        assert_eq!(a.capacity(), 23);
+    }
+    #[test]
+    fn as_heap_bytes_mut() {
+        let mut a = super::String::from("hello");
+        assert!(unsafe { a.as_heap_bytes_mut() }.is_none());
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        unsafe { a.as_heap_bytes_mut() }.unwrap()[..3].make_ascii_uppercase();
+        assert_eq!(a, "ABCdefghijklmnopqrstuvwxyz");
+    }
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn reserve_overflow() {
+        let mut a = super::String::from("hello");
+        a.reserve(usize::MAX);
+    }
+    #[test]
+    fn is_char_boundary() {
+        let a = super::String::from("a€b");
+        for &idx in &[0, 1, 4, 5] {
+            assert!(a.is_char_boundary(idx));
+        }
+        for &idx in &[2, 3, 6] {
+            assert!(!a.is_char_boundary(idx));
+        }
+    }
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn push_str_overflow() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        // Pretend to be nearly full without allocating the memory for it
+        a.len = usize::MAX - 1;
+        a.push_str("ab");
+    }
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn push_overflow() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.len = usize::MAX - 1;
+        a.push('€');
+    }
+    #[test]
+    fn reserve_within_capacity() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        let ptr = a.as_ptr();
+        a.reserve(6);
+        assert_eq!(a.capacity(), 32);
+        assert_eq!(a.as_ptr(), ptr);
+        a.reserve(7);
+        assert_eq!(a.capacity(), 64);
+    }
+    #[test]
+    fn shrink_to_fit_to_stack() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.truncate(23);
+        a.shrink_to_fit();
+        assert!(!a.overflowed());
+        assert_eq!(a.capacity(), 23);
+        assert_eq!(a, "abcdefghijklmnopqrstuvw");
+        let mut a = super::String::from(Box::<str>::from("hello"));
+        assert!(a.overflowed());
+        a.shrink_to_fit();
+        assert!(!a.overflowed());
+        assert_eq!(a, "hello");
+    }
+    #[test]
+    fn clone_from_str() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        let ptr = a.as_ptr();
+        a.clone_from_str("hello");
+        assert_eq!(a, "hello");
+        assert_eq!(a.as_ptr(), ptr);
+        assert_eq!(a.capacity(), 32);
+        let mut a = super::String::from("hello");
+        a.clone_from_str("abcdefghijklmnopqrstuvwxyz");
+        assert_eq!(a, "abcdefghijklmnopqrstuvwxyz");
+    }
+    #[test]
+    fn reset_heap() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.reset();
+        assert_eq!(a, "");
+        assert_eq!(a.capacity(), 23);
+        assert!(!a.overflowed());
+        a.push_str("hello");
+        assert_eq!(a, "hello");
+    }
+    #[test]
+    fn reset_stack() {
+        let mut a = super::String::from("hello");
+        a.reset();
+        assert_eq!(a, "");
+        assert_eq!(a.capacity(), 23);
+    }
+    #[test]
+    fn char_count() {
+        let a = super::String::from("a€b");
+        assert_eq!(a.char_count(0..4), 2);
+        assert_eq!(a.char_count(1..1), 0);
+    }
+    #[test]
+    #[should_panic]
+    fn char_count_not_char_boundary() {
+        let a = super::String::from("a€b");
+        a.char_count(0..2);
+    }
+    #[test]
+    fn debug_matches_std() {
+        let a = super::String::from("hello \"world\"\n");
+        assert_eq!(format!("{:?}", a), format!("{:?}", "hello \"world\"\n"));
+        let a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        assert_eq!(format!("{:?}", a), "\"abcdefghijklmnopqrstuvwxyz\"");
+    }
+    #[test]
+    fn as_bytes_into() {
+        let a = super::String::from("hi");
+        let mut buf = [0; 10];
+        assert_eq!(a.as_bytes_into(&mut buf), Ok(2));
+        assert_eq!(&buf[..3], b"hi\0");
+        let mut buf = [0; 1];
+        assert_eq!(a.as_bytes_into(&mut buf), Err(2));
+        assert_eq!(buf, [0]);
+    }
+    #[cfg(feature = "std")]
+    fn failing_allocs<F: FnOnce()>(f: F) {
+        // Reset the flag even if `f` panics, so later allocations on this
+        // thread still succeed
+        struct Reset;
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                ::allocate::FAIL.with(|fail| fail.set(false));
+            }
+        }
+        let _reset = Reset;
+        ::allocate::FAIL.with(|fail| fail.set(true));
+        f();
+    }
+    #[cfg(feature = "std")]
+    #[test]
+    fn try_reserve_stack_alloc_failure() {
+        let mut a = String::from("Hello!");
+        failing_allocs(|| {
+            assert_eq!(Err(TryReserveError::AllocError { capacity: 128 }), a.try_reserve(100));
+        });
+        assert!(!a.overflowed());
+        assert_eq!(a, "Hello!");
+    }
+    #[cfg(feature = "std")]
+    #[test]
+    fn try_reserve_heap_alloc_failure() {
+        let mut a = String::from("Hello, world! This is on the heap.");
+        let capacity = a.capacity();
+        failing_allocs(|| {
+            assert!(a.try_reserve(100).is_err());
+        });
+        assert!(a.overflowed());
+        assert_eq!(a.capacity(), capacity);
+        assert_eq!(a, "Hello, world! This is on the heap.");
+        a.push_str(" More!");
+        assert_eq!(a, "Hello, world! This is on the heap. More!");
+    }
+    #[test]
+    fn try_reserve_overflow() {
+        let mut a = String::from("Hello!");
+        assert_eq!(Err(TryReserveError::CapacityOverflow), a.try_reserve(usize::MAX));
+        assert_eq!(Err(TryReserveError::CapacityOverflow), a.try_reserve(isize::MAX as usize));
+        assert_eq!(a, "Hello!");
+        assert_eq!(Ok(()), a.try_reserve(30));
+        assert!(a.capacity() >= 36);
+    }
+    #[test]
+    fn retain_reporting() {
+        let mut a = String::from("héllo wörld, this is on the heap");
+        assert!(a.overflowed());
+        assert_eq!(a.retain_reporting(|c| c.is_ascii()), (2, false));
+        assert!(a.overflowed());
+        assert_eq!(a.retain_reporting(|c| c != ' '), (6, false));
+        assert_eq!(a, "hllowrld,thisisontheheap");
+        assert_eq!(a.retain_reporting(|c| c != 'h'), (4, true));
+        assert_eq!(a, "llowrld,tisisonteeap");
+        assert!(!a.overflowed());
+        assert_eq!(a.retain_reporting(|_| true), (0, false));
+    }
+    #[test]
+    fn from_utf8_keeps_capacity() {
+        let mut bytes = Vec::with_capacity(100);
+        bytes.extend_from_slice(b"hello");
+        let ptr = bytes.as_ptr();
+        let mut a = String::from_utf8(bytes).unwrap();
+        assert_eq!(a.capacity(), 100);
+        assert_eq!(a.as_ptr(), ptr);
+        a.push_str(" world");
+        assert_eq!(a.as_ptr(), ptr);
+        assert_eq!(a, "hello world");
+    }
+    #[test]
+    fn insert() {
+        let mut a = String::from("hllo");
+        a.insert(1, 'e');
+        assert_eq!(a, "hello");
+        a.insert(0, '¡');
+        a.insert(a.len(), '€');
+        assert_eq!(a, "¡hello€");
+    }
+    #[test]
+    fn insert_stack_to_heap() {
+        let mut a = String::from("abcdefghijklmnopqrstuvw");
+        assert!(!a.overflowed());
+        a.insert(3, '💖');
+        assert!(a.overflowed());
+        assert_eq!(a, "abc💖defghijklmnopqrstuvw");
+        a.insert(a.len(), 'x');
+        assert_eq!(a, "abc💖defghijklmnopqrstuvwx");
     }
-
-    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
-    where
-        E: Error,
-    {
-        Ok(String::from(v.to_owned()))
+    #[test]
+    #[should_panic]
+    fn insert_not_char_boundary() {
+        let mut a = String::from("€");
+        a.insert(1, 'a');
     }
-
-    fn visit_string<E>(self, v: std::string::String) -> Result<Self::Value, E>
-    where
-        E: Error,
-    {
-        Ok(String::from_string(v))
+    #[test]
+    #[should_panic]
+    fn insert_out_of_bounds() {
+        let mut a = String::from("abc");
+        a.insert(4, 'a');
     }
-
-    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
-    where
-        E: Error,
-    {
-        use std::str;
-        match str::from_utf8(v) {
-            Ok(s) => Ok(String::from(s.to_owned())),
-            Err(_) => Err(Error::invalid_value(Unexpected::Bytes(v), &self)),
-        }
+    #[test]
+    fn eq_char() {
+        assert!(String::from("€") == '€');
+        assert!(!String::from("ab").eq_char('a'));
+        assert!(String::from("a").eq_char('a'));
+        assert!(String::from("a") != 'b');
+        assert!(String::new() != 'a');
+        assert!(String::from("ab") > 'a');
+        assert!(String::from("a") < 'b');
+        assert!(String::from("€") <= '€');
     }
-
-    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
-    where
-        E: Error,
-    {
-        match String::from_utf8(v) {
-            Ok(s) => Ok(s),
-            Err(e) => Err(Error::invalid_value(
-                Unexpected::Bytes(&e.into_bytes()),
-                &self,
-            )),
-        }
+    #[test]
+    fn insert_str() {
+        let mut a = String::from("world");
+        a.insert_str(0, "hello ");
+        assert_eq!(a, "hello world");
+        a.insert_str(a.len(), "!");
+        assert_eq!(a, "hello world!");
+        a.insert_str(5, ",");
+        assert_eq!(a, "hello, world!");
+        a.insert_str(3, "");
+        assert_eq!(a, "hello, world!");
+        assert!(!a.overflowed());
     }
-}
-
-#[cfg(all(feature = "serde", feature = "std"))]
-impl<'a, 'de> Visitor<'de> for StringInPlaceVisitor<'a> {
-    type Value = ();
-
-    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.write_str("a string")
+    #[test]
+    fn insert_str_stack_to_heap() {
+        let mut a = String::from("hello world");
+        a.insert_str(6, "wonderful, beautiful ");
+        assert!(a.overflowed());
+        assert_eq!(a, "hello wonderful, beautiful world");
+        a.insert_str(0, "oh, ");
+        assert_eq!(a, "oh, hello wonderful, beautiful world");
     }
-
-    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
-    where
-        E: Error,
-    {
-        self.0.clear();
-        self.0.push_str(v);
-        Ok(())
+    #[test]
+    #[should_panic]
+    fn insert_str_not_char_boundary() {
+        let mut a = String::from("💖");
+        a.insert_str(2, "a");
     }
-
-    fn visit_string<E>(self, v: std::string::String) -> Result<Self::Value, E>
-    where
-        E: Error,
-    {
-        *self.0 = String::from_string(v);
-        Ok(())
+    #[test]
+    fn frame_by_bytes() {
+        let a = String::from("aaa€");
+        assert_eq!(a.frame_by_bytes(3), vec!["aaa", "€"]);
+        assert_eq!(a.frame_by_bytes(6), vec!["aaa€"]);
+        assert_eq!(a.frame_by_bytes(1), vec!["a", "a", "a", "€"]);
+        assert_eq!(a.frame_by_bytes(0), vec!["a", "a", "a", "€"]);
+        assert_eq!(String::from("€€a").frame_by_bytes(4), vec!["€", "€a"]);
+        assert!(String::new().frame_by_bytes(3).is_empty());
     }
-
-    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
-    where
-        E: Error,
-    {
-        use std::str;
-        match str::from_utf8(v) {
-            Ok(s) => {
-                self.0.clear();
-                self.0.push_str(s);
-                Ok(())
-            }
-            Err(_) => Err(Error::invalid_value(Unexpected::Bytes(v), &self)),
-        }
+    #[test]
+    fn debug_alternate() {
+        let mut a = super::String::from("hello");
+        assert_eq!(format!("{:#?}", a), "String {\n    storage: \"stack\",\n    len: 5,\n    capacity: 23,\n    value: \"hello\",\n}");
+        a.reserve(24);
+        assert_eq!(format!("{:#?}", a), "String {\n    storage: \"heap\",\n    len: 5,\n    capacity: 32,\n    value: \"hello\",\n}");
+        assert_eq!(format!("{:?}", a), "\"hello\"");
     }
-
-    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
-    where
-        E: Error,
-    {
-        match String::from_utf8(v) {
-            Ok(s) => {
-                *self.0 = s;
-                Ok(())
-            }
-            Err(e) => Err(Error::invalid_value(
-                Unexpected::Bytes(&e.into_bytes()),
-                &self,
-            )),
+    #[test]
+    fn drain() {
+        let mut a = String::from("αβγ hello");
+        assert_eq!(a.drain(2..6).collect::<super::String>(), "βγ");
+        assert_eq!(a, "α hello");
+        assert_eq!(a.drain(..=2).collect::<super::String>(), "α ");
+        assert_eq!(a, "hello");
+        a.drain(..);
+        assert_eq!(a, "");
+    }
+    #[test]
+    fn drain_partial() {
+        let mut a = String::from("abcdefghijklmnopqrstuvwxyz");
+        {
+            let mut drain = a.drain(3..10);
+            assert_eq!(drain.next(), Some('d'));
+            assert_eq!(drain.next_back(), Some('j'));
+            assert_eq!(drain.as_str(), "efghi");
         }
+        assert_eq!(a, "abcklmnopqrstuvwxyz");
+        a.drain(1..3);
+        assert_eq!(a, "aklmnopqrstuvwxyz");
+        ::std::mem::forget(a.drain(..5));
+        assert_eq!(a, "aklmnopqrstuvwxyz");
     }
-}
-
-#[cfg(all(feature = "serde", feature = "std"))]
-impl<'de> Deserialize<'de> for String {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        deserializer.deserialize_string(StringVisitor)
+    #[test]
+    #[should_panic]
+    fn drain_not_char_boundary() {
+        let mut a = String::from("αβγ");
+        a.drain(1..4);
     }
-
-    fn deserialize_in_place<D>(deserializer: D, place: &mut Self) -> Result<(), D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        deserializer.deserialize_string(StringInPlaceVisitor(place))
+    #[test]
+    #[should_panic]
+    fn drain_out_of_bounds() {
+        let mut a = String::from("abc");
+        a.drain(1..4);
     }
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
     #[test]
-    fn str_under_24() {
-        assert_eq!("hello", String::from("hello"))
+    fn from_iter_string_refs() {
+        let parts = [String::from("hello"), String::from(", "), String::from("wonderful world!")];
+        let a: String = parts.iter().collect();
+        assert_eq!(a, "hello, wonderful world!");
+        assert_eq!(parts[0], "hello");
+        let a: String = parts[..0].iter().collect();
+        assert_eq!(a, "");
     }
     #[test]
-    fn str_over_24() {
-        assert_eq!("abcdefghijklmnopqrstuvwxyz",
-                   String::from("abcdefghijklmnopqrstuvwxyz"))
+    fn replace_range() {
+        let mut a = String::from("hello {name}!");
+        a.replace_range(6..12, "bob");
+        assert_eq!(a, "hello bob!");
+        a.replace_range(6..9, "sue");
+        assert_eq!(a, "hello sue!");
+        a.replace_range(..5, "");
+        assert_eq!(a, " sue!");
+        a.replace_range(a.len().., "?");
+        assert_eq!(a, " sue!?");
+        assert!(!a.overflowed());
     }
     #[test]
-    fn sort() {
-        let mut v = vec![
-            String::from("c"), String::from("b"), String::from("a"),
-            String::from("d"), String::from("e")
-        ];
-        v.sort();
-        assert_eq!(v,
-                   [String::from("a"), String::from("b"),
-                    String::from("c"), String::from("d"),
-                    String::from("e")]);
+    fn replace_range_stack_to_heap() {
+        let mut a = String::from("hello {name}!");
+        a.replace_range(6..=11, "a very long name indeed");
+        assert!(a.overflowed());
+        assert_eq!(a, "hello a very long name indeed!");
+        a.replace_range(6..29, "x");
+        assert_eq!(a, "hello x!");
     }
     #[test]
-    fn clone_stack() {
-        let a = super::String::from("hello");
-        assert_eq!(a.clone(), a);
+    #[should_panic]
+    fn replace_range_not_char_boundary() {
+        let mut a = String::from("αβγ");
+        a.replace_range(..1, "a");
     }
+    #[cfg(feature = "std")]
     #[test]
-    fn clone_heap() {
-        let a = super::String::from("abcdefghijklmnopqrstuvwxyz");
-        assert_eq!(a.clone(), a);
+    fn try_push_str_alloc_failure() {
+        let mut a = String::from("Hello,");
+        failing_allocs(|| {
+            assert_eq!(Ok(()), a.try_push_str(" world"));
+            assert_eq!(Ok(()), a.try_push('!'));
+            assert!(a.try_push_str(" This no longer fits inline.").is_err());
+        });
+        assert!(!a.overflowed());
+        assert_eq!(a, "Hello, world!");
+        let mut a = String::from("Hello, world! This is on the heap.");
+        let capacity = a.capacity();
+        failing_allocs(|| {
+            assert!(a.try_push_str(" More text to spill past the capacity").is_err());
+        });
+        assert_eq!(a.capacity(), capacity);
+        assert_eq!(a, "Hello, world! This is on the heap.");
+        assert_eq!(Ok(()), a.try_push_str(" More!"));
+        assert_eq!(a, "Hello, world! This is on the heap. More!");
     }
     #[test]
-    fn push_stack() {
-        let mut a = super::String::from("hell");
-        a.push('o');
-        assert_eq!("hello", a)
+    fn split_off() {
+        let mut a = String::from("abcdefghijklmnopqrstuvwxyz €");
+        let capacity = a.capacity();
+        let b = a.split_off(10);
+        assert_eq!(a, "abcdefghij");
+        assert_eq!(b, "klmnopqrstuvwxyz €");
+        assert!(a.overflowed());
+        assert_eq!(a.capacity(), capacity);
+        assert!(!b.overflowed());
+        let c = a.split_off(10);
+        assert_eq!(c, "");
+        let d = a.split_off(0);
+        assert_eq!(a, "");
+        assert_eq!(d, "abcdefghij");
     }
     #[test]
-    fn push_stack_to_heap() {
-        let mut a = super::String::from("abcdefghijklmnopqrstuvw");
-        a.push('x');
-        assert_eq!("abcdefghijklmnopqrstuvwx", a)
+    #[should_panic]
+    fn split_off_not_char_boundary() {
+        let mut a = String::from("a€");
+        a.split_off(2);
     }
     #[test]
-    fn push_heap() {
-        let mut a = super::String::from("abcdefghijklmnopqrstuvwxy");
-        a.push('z');
-        assert_eq!("abcdefghijklmnopqrstuvwxyz", a)
+    #[should_panic]
+    fn split_off_out_of_bounds() {
+        let mut a = String::from("abc");
+        a.split_off(4);
     }
     #[test]
-    fn push_str_stack() {
-        let mut a = super::String::from("h");
-        a.push_str("ello");
-        assert_eq!("hello", a)
+    fn from_utf16() {
+        let v: std::vec::Vec<u16> = "hello, wörld".encode_utf16().collect();
+        let a = String::from_utf16(&v).unwrap();
+        assert_eq!(a, "hello, wörld");
+        assert!(!a.overflowed());
+        let v: std::vec::Vec<u16> = "𝄞💖 astral characters on the heap".encode_utf16().collect();
+        let a = String::from_utf16(&v).unwrap();
+        assert_eq!(a, "𝄞💖 astral characters on the heap");
+        assert!(a.overflowed());
+        assert_eq!(String::from_utf16(&[]).unwrap(), "");
     }
     #[test]
-    fn push_str_heap() {
-        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
-        a.push_str(" hello");
-        assert_eq!("abcdefghijklmnopqrstuvwxyz hello", a)
+    fn from_utf16_lone_surrogate() {
+        assert!(String::from_utf16(&[0x0061, 0xD800]).is_err());
+        assert!(String::from_utf16(&[0xDD1E, 0x0061]).is_err());
+        let err = String::from_utf16(&[0xD834, 0x0061]).unwrap_err();
+        assert_eq!(format!("{}", err), "invalid utf-16: lone surrogate found");
     }
     #[test]
-    fn push_str_stack_to_heap() {
-        let mut a = super::String::from("abcdefghijkl");
-        a.push_str("mnopqrstuvwxyz hello");
-        assert_eq!("abcdefghijklmnopqrstuvwxyz hello", a)
+    fn heap_bytes_allocated() {
+        let mut a = String::from("hello");
+        assert_eq!(a.heap_bytes_allocated(), 0);
+        a.push_str(", this no longer fits inline");
+        assert_eq!(a.heap_bytes_allocated(), a.capacity());
+        assert_eq!(String::with_capacity(64).heap_bytes_allocated(), 64);
     }
     #[test]
-    fn grow_heap() {
+    fn insert_spill_midpoint() {
+        let mut a = String::from("abcdefghijklmnopqrstuvw");
+        a.insert(11, '€');
+        assert!(a.overflowed());
+        assert_eq!(a.capacity(), 32);
+        assert_eq!(a, "abcdefghijk€lmnopqrstuvw");
+        let mut a = String::from("abcdefghijklmnopqrst");
+        a.insert_str(20, "uvwxyz");
+        assert_eq!(a, "abcdefghijklmnopqrstuvwxyz");
+        let mut a = String::from("ghijklmnopqrstuvwxyz");
+        a.insert_str(0, "abcdef");
+        assert_eq!(a, "abcdefghijklmnopqrstuvwxyz");
+    }
+    #[test]
+    fn from_utf16_lossy() {
+        let v: std::vec::Vec<u16> = "just some ascii text!".encode_utf16().collect();
+        let a = String::from_utf16_lossy(&v);
+        assert_eq!(a, "just some ascii text!");
+        assert!(!a.overflowed());
+        let v = [0xD834, 0xDD1E, 0x006d, 0xD800, 0x0075, 0xDD1E, 0xD834, 0xD834, 0xDD1E, 0x0073, 0xDC00];
+        let a = String::from_utf16_lossy(&v);
+        assert_eq!(a.as_bytes(), std::string::String::from_utf16_lossy(&v).as_bytes());
+        assert_eq!(a, "𝄞m\u{FFFD}u\u{FFFD}\u{FFFD}𝄞s\u{FFFD}");
+    }
+    #[test]
+    fn matches_owned() {
+        let a = String::from("banana");
+        let matches: std::vec::Vec<super::String> = a.matches_owned('a').collect();
+        drop(a);
+        assert_eq!(matches, vec!["a", "a", "a"]);
+        let a = String::from("aaaa");
+        assert_eq!(a.matches_str_owned("aa").collect::<std::vec::Vec<_>>(), vec!["aa", "aa"]);
+        assert_eq!(a.matches_owned('b').count(), 0);
+    }
+    #[test]
+    fn from_utf8_lossy() {
+        let a = String::from_utf8_lossy(b"hello world");
+        assert_eq!(a, "hello world");
+        assert!(!a.overflowed());
+        let a = String::from_utf8_lossy("wörld €💖".as_bytes());
+        assert_eq!(a, "wörld €💖");
+        let inputs: [&[u8]; 5] = [
+            b"a\xC3(b",
+            b"\xE2\x82",
+            b"\xF0\x90\x80\xFF\x80abc",
+            b"\xED\xA0\x80 surrogate, and more text afterwards",
+            b"\xC0\xAF",
+        ];
+        for input in inputs.iter() {
+            let a = String::from_utf8_lossy(input);
+            assert_eq!(a, &*std::string::String::from_utf8_lossy(input));
+        }
+        assert_eq!(String::from_utf8_lossy(b"a\xC3(b"), "a\u{FFFD}(b");
+    }
+    #[test]
+    fn trim_matches_char_owned() {
+        let a = String::from("xxhixx");
+        assert_eq!(a.trim_matches_char_owned('x'), "hi");
+        assert_eq!(a.trim_matches_char_owned('h'), "xxhixx");
+        assert_eq!(String::from("xxxx").trim_matches_char_owned('x'), "");
+        let a = String::from("€€ a string that lives on the heap €€");
+        let b = a.trim_matches_char_owned('€');
+        assert_eq!(b, " a string that lives on the heap ");
+    }
+    #[test]
+    fn into_boxed_str() {
+        let a = super::String::from("hello");
+        let b = a.into_boxed_str();
+        assert_eq!(b.len(), 5);
+        assert_eq!(&*b, "hello");
         let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
-        a.push_str(" hello thing");
-        assert_eq!("abcdefghijklmnopqrstuvwxyz hello thing", a)
+        a.reserve(100);
+        let b = a.into_boxed_str();
+        assert_eq!(b.len(), 26);
+        assert_eq!(&*b, "abcdefghijklmnopqrstuvwxyz");
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.shrink_to_fit();
+        let ptr = a.as_ptr();
+        let b = a.into_boxed_str();
+        assert_eq!(b.as_ptr(), ptr);
+        let mut a = super::String::with_capacity(64);
+        a.push_str("");
+        assert_eq!(a.into_boxed_str().len(), 0);
     }
     #[test]
-    fn into_bytes_stack() {
+    fn push_json_escaped() {
+        let mut dest = super::String::new();
+        super::String::from("a\"b\n").push_json_escaped(&mut dest);
+        assert_eq!(dest, "a\\\"b\\n");
+        let a = super::String::from("tab\there \\ bell\u{7} nul\u{0} del\u{7f} é\u{8}\u{c}\r💖");
+        let mut dest = super::String::from("\"");
+        a.push_json_escaped(&mut dest);
+        dest.push('"');
+        assert_eq!(dest, "\"tab\\there \\\\ bell\\u0007 nul\\u0000 del\u{7f} é\\b\\f\\r💖\"");
+    }
+    #[test]
+    fn into_string() {
         let a = super::String::from("hello");
-        assert_eq!(a.into_bytes(), vec![104, 101, 108, 108, 111, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0])
+        let b = a.into_string();
+        assert_eq!(b, "hello");
+        assert_eq!(b.capacity(), 5);
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.reserve(10);
+        let (ptr, capacity) = (a.as_ptr(), a.capacity());
+        let b = a.into_string();
+        assert_eq!(b, "abcdefghijklmnopqrstuvwxyz");
+        assert_eq!(b.as_ptr(), ptr);
+        assert_eq!(b.capacity(), capacity);
+    }
+    #[cfg(feature = "debug-alloc")]
+    thread_local! {
+        static GROWTHS: ::std::cell::RefCell<std::vec::Vec<(usize, usize)>> =
+            const { ::std::cell::RefCell::new(std::vec::Vec::new()) };
     }
+    #[cfg(feature = "debug-alloc")]
     #[test]
-    fn into_bytes_heap() {
-        let a = super::String::from("abcdefghijklmnopqrstuvwxyz");
-        assert_eq!(a.into_bytes(), vec![97, 98, 99, 100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 116, 117, 118, 119, 120, 121, 122])
+    fn growth_hook() {
+        fn record(old: usize, new: usize) {
+            GROWTHS.with(|g| g.borrow_mut().push((old, new)));
+        }
+        set_growth_hook(Some(record));
+        let mut a = super::String::from("hello");
+        a.push_str(" world");
+        a.push_str(", this no longer fits");
+        a.push_str(" and this needs a bigger buffer");
+        a.reserve(100);
+        set_growth_hook(None);
+        a.reserve(1000);
+        GROWTHS.with(|g| assert_eq!(*g.borrow(), vec![(23, 32), (32, 64), (64, 256)]));
     }
     #[test]
-    fn clear_heap() {
+    fn reserve_exact() {
         let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
-        let original_capacity = a.capacity();
+        a.reserve_exact(100);
+        assert_eq!(a.capacity(), 126);
+        a.reserve_exact(50);
+        assert_eq!(a.capacity(), 126);
+        a.reserve_exact(101);
+        assert_eq!(a.capacity(), 127);
+        let mut a = super::String::from("hello");
+        a.reserve_exact(10);
+        assert!(!a.overflowed());
+        a.reserve_exact(20);
+        assert!(a.overflowed());
+        assert_eq!(a.capacity(), 25);
+        assert_eq!(a, "hello");
+    }
+    #[test]
+    fn set_capacity() {
+        let mut a = super::String::from("0123456789");
+        a.set_capacity(20);
+        assert!(!a.overflowed());
+        a.set_capacity(100);
+        assert_eq!(a.capacity(), 100);
+        a.set_capacity(50);
+        assert_eq!(a.capacity(), 50);
+        a.set_capacity(10);
+        assert_eq!(a.capacity(), 10);
+        assert!(a.overflowed());
+        assert_eq!(a, "0123456789");
         a.clear();
+        a.set_capacity(0);
+        assert!(!a.overflowed());
+    }
+    #[test]
+    #[should_panic]
+    fn set_capacity_below_len() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.set_capacity(25);
+    }
+    #[test]
+    fn shrink_to() {
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.reserve(100);
+        assert_eq!(a.capacity(), 128);
+        a.shrink_to(200);
+        assert_eq!(a.capacity(), 128);
+        a.shrink_to(64);
+        assert_eq!(a.capacity(), 64);
+        a.shrink_to(10);
+        assert_eq!(a.capacity(), 26);
+        assert_eq!(a, "abcdefghijklmnopqrstuvwxyz");
+        a.truncate(5);
+        a.shrink_to(10);
+        assert!(!a.overflowed());
+        assert_eq!(a, "abcde");
+        a.shrink_to(0);
+        assert_eq!(a.capacity(), 23);
+    }
+    #[test]
+    fn repeat() {
+        let a = super::String::from("ab");
+        let b = a.repeat(3);
+        assert_eq!(b, "ababab");
+        assert!(!b.overflowed());
+        assert_eq!(a.repeat(0), "");
+        assert_eq!(super::String::new().repeat(5), "");
+        assert_eq!(a.repeat(1), "ab");
+        let b = a.repeat(12);
+        assert_eq!(b, "abababababababababababab");
+        assert!(b.overflowed());
+        assert_eq!(b.capacity(), 24);
+        let a = super::String::from("€💖");
+        assert_eq!(a.repeat(7), "€💖".repeat(7));
+    }
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn repeat_overflow() {
+        super::String::from("ab").repeat(usize::MAX / 2 + 1);
+    }
+    #[test]
+    fn validate_utf8() {
+        assert_eq!(super::String::validate_utf8(b"hello"), Ok(()));
+        assert_eq!(super::String::validate_utf8(b""), Ok(()));
+        assert_eq!(super::String::validate_utf8(&[104, 255]), Err(1));
+        assert_eq!(super::String::validate_utf8(b"ab\xE2\x82"), Err(2));
+        assert_eq!(super::String::validate_utf8(b"\xC3(b"), Err(0));
+    }
+    #[test]
+    fn to_ascii_case() {
+        let a = super::String::from("Hi");
+        let b = a.to_ascii_uppercase();
+        assert_eq!(b, "HI");
+        assert!(!b.overflowed());
+        assert_eq!(a.to_ascii_lowercase(), "hi");
+        assert_eq!(a, "Hi");
+        let a = super::String::from("Mixed Case On The Heap, Ünïcode");
+        assert_eq!(a.to_ascii_uppercase(), "MIXED CASE ON THE HEAP, ÜNïCODE");
+        assert_eq!(a.to_ascii_lowercase(), "mixed case on the heap, Ünïcode");
+    }
+    #[test]
+    fn min_max_ref() {
+        let a = super::String::from("apple");
+        let b = super::String::from("banana");
+        assert!(::std::ptr::eq(a.min_ref(&b), &a));
+        assert!(::std::ptr::eq(b.min_ref(&a), &a));
+        assert!(::std::ptr::eq(a.max_ref(&b), &b));
+        assert!(::std::ptr::eq(b.max_ref(&a), &b));
+        let c = super::String::from("apple");
+        assert!(::std::ptr::eq(a.min_ref(&c), &a));
+        assert!(::std::ptr::eq(a.max_ref(&c), &c));
+    }
+    #[test]
+    fn to_case() {
+        let a = super::String::from("Hello");
+        let b = a.to_uppercase();
+        assert_eq!(b, "HELLO");
+        assert!(!b.overflowed());
+        assert_eq!(a.to_lowercase(), "hello");
+        let a = super::String::from("straße ßß");
+        assert_eq!(a.to_uppercase(), "STRASSE SSSS");
+        let a = super::String::from("ΐΐΐΐΐ");
+        assert!(!a.overflowed());
+        let b = a.to_uppercase();
+        assert_eq!(b, "ΐΐΐΐΐ".to_uppercase());
+        assert!(b.overflowed());
+        let a = super::String::from("İİİİİİİİİİİ");
+        assert!(!a.overflowed());
+        let b = a.to_lowercase();
+        assert_eq!(b, "i\u{307}".repeat(11));
+        assert!(b.overflowed());
+    }
+    #[test]
+    fn push_str_bounded() {
+        let mut a = super::String::from("hello ");
+        a.push_str_bounded("world", 8);
+        assert_eq!(a, "lo world");
+        a.push_str_bounded("!", 8);
+        assert_eq!(a, "o world!");
+        a.push_str_bounded("", 3);
+        assert_eq!(a, "ld!");
+        a.push_str_bounded("€€", 5);
+        assert_eq!(a, "€");
+        a.push_str_bounded("a much longer line that replaces everything", 10);
+        assert_eq!(a, "everything");
+        a.push_str_bounded("💖💖", 5);
+        assert_eq!(a, "💖");
+        a.push_str_bounded("💖", 3);
         assert_eq!(a, "");
-        assert_eq!(a.capacity(), original_capacity);
+        let mut a = super::String::new();
+        for _ in 0..100 {
+            a.push_str_bounded("line of log output\n", 64);
+            assert!(a.len() <= 64);
+        }
+        assert!(a.capacity() <= 64);
+    }
+    #[test]
+    fn replace_owned() {
+        let a = super::String::from("aaaa");
+        let b = a.replace_owned("aa", "b");
+        assert_eq!(b, "bb");
+        assert!(!b.overflowed());
+        assert_eq!(a.replace_owned("aaa", "b"), "ba");
+        assert_eq!(a.replacen_owned("a", "b", 3), "bbba");
+        assert_eq!(a.replacen_owned("a", "b", 0), "aaaa");
+        assert_eq!(a.replace_owned("", "-"), "-a-a-a-a-");
+        assert_eq!(a.replace_owned("x", "y"), "aaaa");
+        let b = a.replace_owned("a", "long replacement ");
+        assert_eq!(b, "aaaa".replace("a", "long replacement "));
+        assert!(b.overflowed());
+        let a = super::String::from("€ a € b €");
+        assert_eq!(a.replace_owned("€", "$"), "$ a $ b $");
+        // Other patterns still reach str::replace through Deref
+        assert_eq!(a.replace('€', "$"), "$ a $ b $");
+        assert_eq!(a.replacen(|c: char| c.is_ascii_alphabetic(), "x", 1), "€ x € b €");
+    }
+    #[test]
+    fn into_boxed_bytes() {
+        let a = super::String::from("hello");
+        let b: Box<[u8]> = a.into();
+        assert_eq!(&*b, b"hello");
+        let mut a = super::String::from("abcdefghijklmnopqrstuvwxyz");
+        a.shrink_to_fit();
+        let ptr = a.as_ptr();
+        let b: Box<[u8]> = a.into();
+        assert_eq!(&*b, &b"abcdefghijklmnopqrstuvwxyz"[..]);
+        assert_eq!(b.as_ptr(), ptr);
+    }
+    #[test]
+    fn extend_from_within() {
+        let mut a = super::String::from("€bc");
+        a.extend_from_within(..);
+        assert_eq!(a, "€bc€bc");
+        assert!(!a.overflowed());
+        a.extend_from_within(..=7);
+        assert_eq!(a, "€bc€bc€bc€");
+        assert!(!a.overflowed());
+        a.extend_from_within(..10);
+        assert!(a.overflowed());
+        assert_eq!(a, "€bc€bc€bc€€bc€bc");
+    }
+    #[test]
+    #[should_panic]
+    fn extend_from_within_not_char_boundary() {
+        let mut a = super::String::from("€bc");
+        a.extend_from_within(1..);
+    }
+    #[test]
+    fn char_column() {
+        let a = super::String::from("a€b");
+        assert_eq!(a.char_column(0), 0);
+        assert_eq!(a.char_column(1), 1);
+        assert_eq!(a.char_column(4), 2);
+        assert_eq!(a.char_column(5), 3);
+    }
+    #[test]
+    #[should_panic]
+    fn char_column_not_char_boundary() {
+        super::String::from("a€b").char_column(2);
+    }
+    #[test]
+    fn ord_str() {
+        let a = super::String::from("apple");
+        assert!(a < "zzz");
+        assert!(a > "aardvark");
+        assert!(a <= "apple");
+        assert!(a >= "apple");
+        assert!(a < *"zzz");
+        assert!("zzz" > a);
+        assert!(*"aardvark" < a);
+        assert_eq!("apple".partial_cmp(&a), Some(::std::cmp::Ordering::Equal));
+        let b = super::String::from("a string that is stored on the heap");
+        assert!(b < "b");
+        assert!("b" > b);
+    }
+    #[test]
+    fn retain_bytes() {
+        let mut a = super::String::from("h e l l o");
+        a.retain_bytes(|b| b != b' ');
+        assert_eq!(a, "hello");
+        let mut a = super::String::from("ünïcödé, with spaces on the heap");
+        a.retain_bytes(|_| false);
+        assert_eq!(a, "üïöé");
+        let mut a = super::String::from("keep everything €");
+        a.retain_bytes(|_| true);
+        assert_eq!(a, "keep everything €");
+    }
+    #[test]
+    fn from_char() {
+        let a = super::String::from('x');
+        assert_eq!(a, "x");
+        assert!(!a.overflowed());
+        let a: super::String = '💖'.into();
+        assert_eq!(a, "💖");
+        assert!(!a.overflowed());
+    }
+    #[test]
+    fn trim_matches_owned() {
+        let a = super::String::from("a///");
+        assert_eq!(a.trim_end_matches_owned("/"), "a");
+        assert_eq!(a.trim_start_matches_owned("/"), "a///");
+        assert_eq!(a.trim_end_matches_owned(""), "a///");
+        let a = super::String::from("https://example.com/some/long/path/");
+        let b = a.trim_start_matches_owned("https://");
+        assert_eq!(b, "example.com/some/long/path/");
+        assert!(b.overflowed());
+        let b = b.trim_end_matches_owned("/some/long/path/");
+        assert_eq!(b, "example.com");
+        assert!(!b.overflowed());
+    }
+    #[test]
+    fn cow_conversions() {
+        let a = super::String::from(Cow::Borrowed("hello"));
+        assert_eq!(a, "hello");
+        assert!(!a.overflowed());
+        let owned = ::std::string::String::from("a string that lives on the heap");
+        let (ptr, capacity) = (owned.as_ptr(), owned.capacity());
+        let a = super::String::from(Cow::Owned(owned));
+        assert_eq!(a, "a string that lives on the heap");
+        assert_eq!(a.as_ptr(), ptr);
+        assert_eq!(a.capacity(), capacity);
+        let b: Cow<str> = a.into();
+        match b {
+            Cow::Owned(ref s) => assert_eq!(s.as_ptr(), ptr),
+            Cow::Borrowed(_) => panic!("expected an owned string"),
+        }
+        assert_eq!(b, "a string that lives on the heap");
+        let c: Cow<str> = super::String::from("short").into();
+        assert_eq!(c, "short");
+    }
+    #[test]
+    fn with_capacity_aligned() {
+        let mut s = super::String::with_capacity_aligned(8, 32);
+        assert_eq!(s.as_ptr() as usize % 32, 0);
+        assert!(s.capacity() >= 32);
+        assert_eq!(::std::mem::size_of::<super::String>(), 32);
+        // Growing past the capacity keeps the alignment
+        for _ in 0..10 {
+            s.push_str("abcdefghijklmnopqrstuvwxyz");
+        }
+        assert_eq!(s.as_ptr() as usize % 32, 0);
+        assert_eq!(s.len(), 260);
+        let clone = s.clone();
+        assert_eq!(clone.as_ptr() as usize % 32, 0);
+        assert_eq!(clone, s);
+        s.truncate(100);
+        s.shrink_to(100);
+        assert_eq!(s.as_ptr() as usize % 32, 0);
+        assert_eq!(s.capacity(), 100);
+        let bytes = s.clone().into_bytes();
+        assert_eq!(&bytes[..], s.as_bytes());
+        let boxed = s.clone().into_boxed_str();
+        assert_eq!(&*boxed, s.as_str());
+        // Moving back onto the stack frees the aligned buffer
+        s.truncate(3);
+        s.shrink_to_fit();
+        assert_eq!(s, "abc");
+    }
+    #[test]
+    #[should_panic]
+    fn with_capacity_aligned_not_power_of_two() {
+        super::String::with_capacity_aligned(16, 24);
+    }
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn with_capacity_aligned_overflow() {
+        super::String::with_capacity_aligned(16, isize::MAX as usize + 1);
+    }
+    #[test]
+    #[should_panic(expected = "capacity overflow")]
+    fn with_capacity_aligned_overflow_capacity() {
+        super::String::with_capacity_aligned(usize::MAX - 8, 32);
+    }
+    #[test]
+    fn try_reserve_aligned_overflow() {
+        let mut a = super::String::with_capacity_aligned(32, 32);
+        a.push_str("hello");
+        assert_eq!(a.try_reserve_exact(isize::MAX as usize - 8), Err(super::TryReserveError::CapacityOverflow));
+        assert_eq!(a, "hello");
+        assert_eq!(a.as_ptr() as usize % 32, 0);
+    }
+    #[test]
+    fn clone_aligned_short() {
+        let mut a = super::String::with_capacity_aligned(32, 32);
+        a.push_str("hello");
+        let b = a.clone();
+        assert_eq!(b, "hello");
+        assert_eq!(b.capacity(), 23);
     }
 }